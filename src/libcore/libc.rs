@@ -1587,23 +1587,33 @@ pub mod funcs {
     #[cfg(target_os = "macos")]
     #[nolink]
     pub mod extra {
-        use libc::types::os::arch::c95::{c_char, c_int};
+        use libc::types::common::c95::{c_void};
+        use libc::types::os::arch::c95::{c_char, c_int, size_t};
 
         #[abi = "cdecl"]
         pub extern {
             unsafe fn _NSGetExecutablePath(buf: *mut c_char,
                                            bufsize: *mut u32)
                                         -> c_int;
+            unsafe fn memmem(haystack: *c_void, haystacklen: size_t,
+                             needle: *c_void, needlelen: size_t)
+                          -> *c_void;
         }
     }
 
-    #[cfg(target_os = "freebsd")]
-    pub mod extra {
-    }
-
     #[cfg(target_os = "linux")]
     #[cfg(target_os = "android")]
+    #[cfg(target_os = "freebsd")]
     pub mod extra {
+        use libc::types::common::c95::{c_void};
+        use libc::types::os::arch::c95::{size_t};
+
+        #[abi = "cdecl"]
+        pub extern {
+            unsafe fn memmem(haystack: *c_void, haystacklen: size_t,
+                             needle: *c_void, needlelen: size_t)
+                          -> *c_void;
+        }
     }
 
 
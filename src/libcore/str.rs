@@ -22,10 +22,12 @@ use cast;
 use char;
 use clone::Clone;
 use cmp::{Equiv, TotalOrd, Ordering, Less, Equal, Greater};
+use int;
 use libc;
 use option::{None, Option, Some};
 use ptr;
 use str;
+use sys;
 use u8;
 use uint;
 use vec;
@@ -49,6 +51,63 @@ pub fn from_bytes(vv: &[const u8]) -> ~str {
     return unsafe { raw::from_bytes(vv) };
 }
 
+/**
+ * Convert a vector of bytes to a UTF-8 string, returning `none` if the
+ * bytes are not valid UTF-8
+ */
+pub fn from_bytes_opt(vv: &[const u8]) -> Option<~str> {
+    if is_utf8(vv) {
+        Some(unsafe { raw::from_bytes(vv) })
+    } else {
+        None
+    }
+}
+
+/**
+ * Convert a vector of bytes to a UTF-8 string without copying, returning
+ * `none` if the bytes are not valid UTF-8
+ *
+ * The vector is consumed and its buffer reused, with a null terminator
+ * pushed after the bytes. This is the inverse of `into_bytes`.
+ */
+pub fn from_bytes_owned(v: ~[u8]) -> Option<~str> {
+    if !is_utf8(v) { return None; }
+    let mut v = v;
+    v.push(0u8);
+    Some(unsafe { ::cast::transmute(v) })
+}
+
+/**
+ * Convert a vector of bytes to a UTF-8 string, replacing invalid sequences
+ *
+ * Valid characters are copied verbatim. Each maximal invalid subsequence
+ * is replaced by a single U+FFFD REPLACEMENT CHARACTER: a start byte
+ * together with whatever continuation bytes followed it before the
+ * sequence broke off, or a single byte that cannot start a sequence.
+ */
+pub fn from_bytes_lossy(vv: &[const u8]) -> ~str {
+    let total = vec::len(vv);
+    let mut out = with_capacity(total);
+    let mut i = 0u;
+    while i < total {
+        let w = utf8_char_width(vv[i]);
+        let mut j = i + 1u;
+        while j < i + w && j < total && vv[j] & 192u8 == tag_cont_u8 {
+            j += 1u;
+        }
+        if w != 0u && j == i + w {
+            while i < j {
+                unsafe { raw::push_byte(&mut out, vv[i]); }
+                i += 1u;
+            }
+        } else {
+            push_char(&mut out, '\uFFFD');
+            i = j;
+        }
+    }
+    out
+}
+
 /// Copy a slice into a new unique str
 pub fn from_slice(s: &str) -> ~str {
     unsafe { raw::slice_bytes_unique(s, 0, len(s)) }
@@ -79,16 +138,33 @@ pub fn from_byte(b: u8) -> ~str {
     unsafe { ::cast::transmute(~[b, 0u8]) }
 }
 
+/**
+ * Convert a byte to a UTF-8 string, or `none` if the byte is not ASCII
+ *
+ * Only an ASCII byte is a character by itself; any other byte is part of
+ * a multibyte sequence.
+ */
+pub fn from_byte_opt(b: u8) -> Option<~str> {
+    if b < 128u8 { Some(from_byte(b)) } else { None }
+}
+
+// The number of bytes in the UTF-8 encoding of `ch`
+#[inline(always)]
+fn char_utf8_len(ch: char) -> uint {
+    let code = ch as uint;
+    if code < max_one_b { 1u }
+    else if code < max_two_b { 2u }
+    else if code < max_three_b { 3u }
+    else if code < max_four_b { 4u }
+    else if code < max_five_b { 5u }
+    else { 6u }
+}
+
 /// Appends a character at the end of a string
 pub fn push_char(s: &mut ~str, ch: char) {
     unsafe {
         let code = ch as uint;
-        let nb = if code < max_one_b { 1u }
-        else if code < max_two_b { 2u }
-        else if code < max_three_b { 3u }
-        else if code < max_four_b { 4u }
-        else if code < max_five_b { 5u }
-        else { 6u };
+        let nb = char_utf8_len(ch);
         let len = len(*s);
         let new_len = len + nb;
         reserve_at_least(&mut *s, new_len);
@@ -157,6 +233,39 @@ pub fn from_char(ch: char) -> ~str {
     buf
 }
 
+/// Make a string of `n` copies of `ch`
+pub fn from_char_n(ch: char, n: uint) -> ~str {
+    let mut buf = ~"";
+    if n == 0u { return buf; }
+    reserve(&mut buf, char_utf8_len(ch) * n);
+    for n.times { push_char(&mut buf, ch); }
+    buf
+}
+
+/// Make a string of `n` spaces
+#[inline(always)]
+pub fn spaces(n: uint) -> ~str {
+    from_char_n(' ', n)
+}
+
+/**
+ * Builds a string by calling a provided function with an argument
+ * function that appends a character to the string being built.
+ *
+ * # Example
+ *
+ * ~~~
+ * let s = str::build_str(|push| for "a1b2".each_char |c| {
+ *     if !char::is_digit(c) { push(c) }
+ * });
+ * ~~~
+ */
+pub fn build_str(builder: &fn(push: &fn(char))) -> ~str {
+    let mut buf = ~"";
+    builder(|c| push_char(&mut buf, c));
+    buf
+}
+
 /// Convert a vector of chars to a string
 pub fn from_chars(chs: &[char]) -> ~str {
     let mut buf = ~"";
@@ -204,6 +313,128 @@ pub fn push_str(lhs: &mut ~str, rhs: &str) {
     }
 }
 
+/**
+ * Appends raw bytes to a string if they are valid UTF-8
+ *
+ * Since `s` already holds whole characters, `bytes` is checked on its own;
+ * a fragment holding only part of a character is rejected. Returns true if
+ * the bytes were appended, leaving `s` untouched otherwise.
+ */
+pub fn push_bytes_checked(s: &mut ~str, bytes: &[u8]) -> bool {
+    if !is_utf8(bytes) { return false; }
+    unsafe {
+        let llen = len(*s);
+        let rlen = bytes.len();
+        reserve_at_least(&mut *s, llen + rlen);
+        do as_buf(*s) |sbuf, _slen| {
+            do vec::as_imm_buf(bytes) |bbuf, _blen| {
+                let dst = ptr::offset(sbuf, llen);
+                let dst = ::cast::transmute_mut_unsafe(dst);
+                ptr::copy_memory(dst, bbuf, rlen);
+            }
+        }
+        raw::set_len(s, llen + rlen);
+    }
+    true
+}
+
+/**
+ * Appends `item` to a delimited list, preceded by `sep` unless the string
+ * is still empty
+ *
+ * Repeated calls build the same string as `connect` would.
+ */
+pub fn push_delimited(s: &mut ~str, sep: &str, item: &str) {
+    if len(*s) > 0u {
+        reserve_at_least(&mut *s, len(*s) + len(sep) + len(item));
+        push_str(&mut *s, sep);
+    }
+    push_str(&mut *s, item);
+}
+
+/**
+ * Appends copies of `fill` until the string is `total_chars` characters
+ * long
+ *
+ * A string that is already at least that long is left untouched.
+ */
+pub fn extend_with_char(s: &mut ~str, total_chars: uint, fill: char) {
+    let n = char_len(*s);
+    if n >= total_chars { return; }
+    reserve(&mut *s, len(*s) + (total_chars - n) * char_utf8_len(fill));
+    for uint::range(n, total_chars) |_i| {
+        push_char(&mut *s, fill);
+    }
+}
+
+/**
+ * Shortens a string to at most `max_chars` characters
+ *
+ * A string with no more than `max_chars` characters is left untouched.
+ */
+pub fn truncate_chars(s: &mut ~str, max_chars: uint) {
+    let l = len(*s);
+    let mut end = 0u, n = 0u;
+    while end < l && n < max_chars {
+        end = char_range_at(*s, end).next;
+        n += 1u;
+    }
+    if end < l {
+        unsafe { raw::set_len(s, end); }
+    }
+}
+
+/**
+ * Shortens a string to at most `max_chars` characters, marking the cut
+ * with an ellipsis
+ *
+ * A string with no more than `max_chars` characters is returned as it is.
+ * Otherwise the result is its first `max_chars - 1` characters followed by
+ * '…' (U+2026). As there is no room even for the ellipsis, a `max_chars`
+ * of 0 gives the empty string.
+ */
+pub fn ellipsize(s: &str, max_chars: uint) -> ~str {
+    if char_len(s) <= max_chars { return from_slice(s); }
+    if max_chars == 0u { return ~""; }
+    let cut = count_bytes(s, 0u, max_chars - 1u);
+    let mut out = with_capacity(cut + 3u);
+    unsafe { push_str(&mut out, raw::slice_bytes(s, 0u, cut)); }
+    push_char(&mut out, '\u2026');
+    out
+}
+
+/**
+ * A writer that appends to an owned string
+ *
+ * Lets formatting code build a string directly, without going through
+ * `io::with_str_writer`.
+ */
+pub struct StrWriter {
+    buf: ~str
+}
+
+pub impl StrWriter {
+    /// Create a writer with an empty buffer
+    #[inline(always)]
+    fn new() -> StrWriter { StrWriter { buf: ~"" } }
+
+    /// Appends a string to the buffer
+    #[inline(always)]
+    fn write_str(&mut self, s: &str) { push_str(&mut self.buf, s) }
+
+    /// Appends a character to the buffer
+    #[inline(always)]
+    fn write_char(&mut self, c: char) { push_char(&mut self.buf, c) }
+
+    /// Returns the number of bytes written so far
+    #[inline(always)]
+    fn len(&self) -> uint { len(self.buf) }
+
+    /// Consumes the writer, returning the string written
+    #[inline(always)]
+    fn unwrap(self) -> ~str { let StrWriter { buf: buf } = self; buf }
+}
+
 /// Concatenate two strings together
 #[inline(always)]
 pub fn append(lhs: ~str, rhs: &str) -> ~str {
@@ -251,6 +482,48 @@ pub fn repeat(ss: &str, nn: uint) -> ~str {
     acc
 }
 
+/**
+ * Appends `n` copies of `piece` to `s`
+ *
+ * The space needed is reserved once up front, instead of building a
+ * temporary with `repeat` and appending that.
+ */
+pub fn repeat_into(s: &mut ~str, piece: &str, n: uint) {
+    let new_len = len(*s) + len(piece) * n;
+    reserve(&mut *s, new_len);
+    for n.times { push_str_no_overallocate(&mut *s, piece); }
+}
+
+/**
+ * Moves the first `n` characters of a string to its end
+ *
+ * `n` counts characters, not bytes, and is taken modulo the length of the
+ * string in characters.
+ */
+pub fn rotate_left(s: &str, n: uint) -> ~str {
+    let cl = char_len(s);
+    if cl == 0u { return ~""; }
+    let mid = count_bytes(s, 0u, n % cl);
+    let mut result = with_capacity(len(s));
+    unsafe {
+        push_str(&mut result, raw::slice_bytes(s, mid, len(s)));
+        push_str(&mut result, raw::slice_bytes(s, 0u, mid));
+    }
+    result
+}
+
+/**
+ * Moves the last `n` characters of a string to its start
+ *
+ * `n` counts characters, not bytes, and is taken modulo the length of the
+ * string in characters.
+ */
+pub fn rotate_right(s: &str, n: uint) -> ~str {
+    let cl = char_len(s);
+    if cl == 0u { return ~""; }
+    rotate_left(s, cl - n % cl)
+}
+
 /*
 Section: Adding to and removing from a string
 */
@@ -356,7 +629,62 @@ pub fn trim_chars(s: &'a str, chars_to_trim: &[char]) -> &'a str {
     trim_left_chars(trim_right_chars(s, chars_to_trim), chars_to_trim)
 }
 
-/// Returns a string with leading whitespace removed
+/**
+ * Returns a string with leading characters satisfying `pred` removed.
+ *
+ * # Arguments
+ *
+ * * s - A string
+ * * pred - A function deciding which characters to trim
+ *
+ */
+pub fn trim_left_matches(s: &'a str, pred: &fn(char) -> bool) -> &'a str {
+    match find(s, |c| !pred(c)) {
+      None => "",
+      Some(first) => unsafe { raw::slice_bytes(s, first, s.len()) }
+    }
+}
+
+/**
+ * Returns a string with trailing characters satisfying `pred` removed.
+ *
+ * # Arguments
+ *
+ * * s - A string
+ * * pred - A function deciding which characters to trim
+ *
+ */
+pub fn trim_right_matches(s: &'a str, pred: &fn(char) -> bool) -> &'a str {
+    match rfind(s, |c| !pred(c)) {
+      None => "",
+      Some(last) => {
+        let next = char_range_at(s, last).next;
+        unsafe { raw::slice_bytes(s, 0u, next) }
+      }
+    }
+}
+
+/**
+ * Returns a string with leading and trailing characters satisfying `pred`
+ * removed.
+ *
+ * # Arguments
+ *
+ * * s - A string
+ * * pred - A function deciding which characters to trim
+ *
+ */
+pub fn trim_matches(s: &'a str, pred: &fn(char) -> bool) -> &'a str {
+    trim_left_matches(trim_right_matches(s, pred), pred)
+}
+
+/**
+ * Returns a string with leading whitespace removed
+ *
+ * Whitespace is as defined by `char::is_whitespace`, which includes
+ * Unicode spaces such as U+00A0 (no-break space) and U+3000 (ideographic
+ * space). Use `trim_ascii_left` to strip only ASCII whitespace.
+ */
 pub fn trim_left(s: &'a str) -> &'a str {
     match find(s, |c| !char::is_whitespace(c)) {
       None => "",
@@ -364,7 +692,11 @@ pub fn trim_left(s: &'a str) -> &'a str {
     }
 }
 
-/// Returns a string with trailing whitespace removed
+/**
+ * Returns a string with trailing whitespace removed
+ *
+ * Whitespace is as defined by `char::is_whitespace`; see `trim_left`.
+ */
 pub fn trim_right(s: &'a str) -> &'a str {
     match rfind(s, |c| !char::is_whitespace(c)) {
       None => "",
@@ -375,9 +707,82 @@ pub fn trim_right(s: &'a str) -> &'a str {
     }
 }
 
-/// Returns a string with leading and trailing whitespace removed
+/**
+ * Returns a string with leading and trailing whitespace removed
+ *
+ * Whitespace is as defined by `char::is_whitespace`; see `trim_left`.
+ */
 pub fn trim(s: &'a str) -> &'a str { trim_left(trim_right(s)) }
 
+// The ASCII whitespace set: space, tab, LF, VT, FF and CR
+#[inline(always)]
+fn is_ascii_whitespace(c: char) -> bool {
+    c == ' ' || ('\x09' <= c && c <= '\x0d')
+}
+
+/**
+ * Returns a string with leading ASCII whitespace removed
+ *
+ * Only space, tab, LF, VT, FF and CR are stripped; other Unicode
+ * whitespace such as U+00A0 (no-break space) is kept. This suits
+ * protocols that define their own whitespace.
+ */
+pub fn trim_ascii_left(s: &'a str) -> &'a str {
+    trim_left_matches(s, is_ascii_whitespace)
+}
+
+/// Returns a string with trailing ASCII whitespace removed
+pub fn trim_ascii_right(s: &'a str) -> &'a str {
+    trim_right_matches(s, is_ascii_whitespace)
+}
+
+/// Returns a string with leading and trailing ASCII whitespace removed
+pub fn trim_ascii(s: &'a str) -> &'a str {
+    trim_ascii_left(trim_ascii_right(s))
+}
+
+/**
+ * Returns a string with one trailing line ending removed
+ *
+ * A trailing "\r\n" or '\n' is removed, once; any other whitespace,
+ * including more line endings before it, is kept.
+ */
+pub fn trim_newline(s: &'a str) -> &'a str {
+    let l = len(s);
+    if l > 0u && s[l - 1u] == '\n' as u8 {
+        let end = if l > 1u && s[l - 2u] == '\r' as u8 { l - 2u } else { l - 1u };
+        unsafe { raw::slice_bytes(s, 0u, end) }
+    } else {
+        s
+    }
+}
+
+/**
+ * Returns true if two strings are equal once leading and trailing
+ * whitespace is removed from both
+ *
+ * This compares the slices returned by `trim`, so nothing is allocated.
+ */
+pub fn eq_trimmed(a: &str, b: &str) -> bool {
+    eq_slice(trim(a), trim(b))
+}
+
+/// Returns true if the string starts with a byte order mark (U+FEFF)
+pub fn has_bom(s: &str) -> bool {
+    len(s) > 0u && char_range_at(s, 0u).ch == '\uFEFF'
+}
+
+/// Returns a string with a leading byte order mark (U+FEFF) removed
+pub fn strip_bom(s: &'a str) -> &'a str {
+    if len(s) == 0u { return s; }
+    let CharRange {ch, next} = char_range_at(s, 0u);
+    if ch == '\uFEFF' {
+        unsafe { raw::slice_bytes(s, next, len(s)) }
+    } else {
+        s
+    }
+}
+
 /*
 Section: Transforming strings
 */
@@ -395,6 +800,43 @@ pub fn to_bytes(s: &str) -> ~[u8] {
     }
 }
 
+/**
+ * Converts a string to a vector of bytes without copying
+ *
+ * The string is consumed and its buffer reused. The result vector is not
+ * null-terminated.
+ */
+pub fn into_bytes(s: ~str) -> ~[u8] {
+    unsafe {
+        let l = len(s);
+        let mut v: ~[u8] = ::cast::transmute(s);
+        vec::raw::set_len(&mut v, l);
+        v
+    }
+}
+
+/**
+ * Converts a string to a null-terminated vector of bytes
+ *
+ * The result is the bytes of `s` followed by a single `0u8`, suitable for
+ * handing to C.
+ *
+ * # Failure
+ *
+ * Fails if `s` contains a null byte, since C would see it as the end of
+ * the string.
+ */
+pub fn to_bytes_with_nul(s: &str) -> ~[u8] {
+    let l = len(s);
+    let mut v = vec::with_capacity(l + 1u);
+    for each(s) |b| {
+        fail_unless!(b != 0u8);
+        v.push(b);
+    }
+    v.push(0u8);
+    v
+}
+
 /// Work with the string as a byte slice, not including trailing null.
 #[inline(always)]
 pub fn byte_slice<T>(s: &str, f: &fn(v: &[u8]) -> T) -> T {
@@ -425,6 +867,17 @@ pub fn substr(s: &'a str, begin: uint, n: uint) -> &'a str {
     slice(s, begin, begin + count_bytes(s, begin, n))
 }
 
+/**
+ * Take a substring of another, or `None` if `begin` is not a character
+ * boundary or fewer than `n` characters follow it.
+ */
+pub fn substr_opt(s: &'a str, begin: uint, n: uint) -> Option<&'a str> {
+    match count_bytes_opt(s, begin, n) {
+        Some(cnt) => Some(unsafe { raw::slice_bytes(s, begin, begin + cnt) }),
+        None => None
+    }
+}
+
 /**
  * Returns a slice of the given string from the byte range [`begin`..`end`)
  *
@@ -437,12 +890,50 @@ pub fn slice(s: &'a str, begin: uint, end: uint) -> &'a str {
     unsafe { raw::slice_bytes(s, begin, end) }
 }
 
+/**
+ * Returns a slice of the given string from the character range
+ * [`char_start`..`char_end`)
+ *
+ * Both bounds count characters, not bytes.
+ *
+ * # Failure
+ *
+ * Fails if `char_start` is greater than `char_end` or if `char_end` is
+ * beyond the last character of the string
+ */
+pub fn slice_chars(s: &'a str, char_start: uint, char_end: uint) -> &'a str {
+    fail_unless!(char_start <= char_end);
+    let l = len(s);
+    let mut i = 0u, n = 0u, begin = 0u;
+    while n < char_end {
+        fail_unless!(i < l);
+        if n == char_start { begin = i; }
+        i = char_range_at(s, i).next;
+        n += 1u;
+    }
+    if char_start == char_end { begin = i; }
+    unsafe { raw::slice_bytes(s, begin, i) }
+}
+
 /// Splits a string into substrings at each occurrence of a given
 /// character.
 pub fn split_char(s: &str, sep: char) -> ~[~str] {
     split_char_inner(s, sep, len(s), true, true)
 }
 
+/**
+ * Splits a string at each occurrence of a given character, into a vector
+ * supplied by the caller
+ *
+ * `out` is cleared first, keeping its capacity, and then holds the same
+ * substrings `split_char` would return. Reusing one vector across calls
+ * saves allocating a new one each time.
+ */
+pub fn split_char_into(s: &str, sep: char, out: &mut ~[~str]) {
+    vec::truncate(out, 0u);
+    split_char_inner_into(s, sep, len(s), true, true, out);
+}
+
 /**
  * Splits a string into substrings at each occurrence of a given
  * character up to 'count' times.
@@ -466,31 +957,104 @@ pub fn split_char_no_trailing(s: &str, sep: char) -> ~[~str] {
     split_char_inner(s, sep, len(s), true, false)
 }
 
-fn split_char_inner(s: &str, sep: char, count: uint, allow_empty: bool,
-                    allow_trailing_empty: bool) -> ~[~str] {
-    if sep < 128u as char {
-        let b = sep as u8, l = len(s);
-        let mut result = ~[], done = 0u;
-        let mut i = 0u, start = 0u;
-        while i < l && done < count {
-            if s[i] == b {
-                if allow_empty || start < i {
-                    unsafe {
-                        result.push(raw::slice_bytes_unique(s, start, i));
-                    }
-                }
-                start = i + 1u;
-                done += 1u;
-            }
-            i += 1u;
-        }
-        // only push a non-empty trailing substring
-        if allow_trailing_empty || start < l {
-            unsafe { result.push(raw::slice_bytes_unique(s, start, l) ) };
+/**
+ * Splits a string into substrings, treating `sep` as a terminator
+ *
+ * Each substring is ended by `sep`, which may be left off the last one, so
+ * `"a.b.c."` and `"a.b.c"` both give `~[~"a", ~"b", ~"c"]`. Empty
+ * substrings between separators are kept, and the empty string has no
+ * substrings at all.
+ */
+pub fn split_char_terminator(s: &str, sep: char) -> ~[~str] {
+    split_char_inner(s, sep, len(s), true, false)
+}
+
+/**
+ * Splits a string at each occurrence of `sep`, keeping the separators
+ *
+ * Each separator becomes an element of its own, between the fields around
+ * it, so `"a.b"` gives `~[~"a", ~".", ~"b"]` and concatenating the result
+ * gives back `s`. Empty fields are left out.
+ */
+pub fn split_keep_char(s: &str, sep: char) -> ~[~str] {
+    let l = len(s);
+    let mut result = ~[];
+    let mut i = 0u, start = 0u;
+    while i < l {
+        let CharRange {ch, next} = char_range_at(s, i);
+        if ch == sep {
+            unsafe {
+                if start < i {
+                    result.push(raw::slice_bytes_unique(s, start, i));
+                }
+                result.push(raw::slice_bytes_unique(s, i, next));
+            }
+            start = next;
         }
-        result
+        i = next;
+    }
+    if start < l {
+        unsafe { result.push(raw::slice_bytes_unique(s, start, l)); }
+    }
+    result
+}
+
+/**
+ * Like `splitn_char`, but also returns the number of separators consumed
+ *
+ * The count is less than `count` only if the string ran out of
+ * separators, so `("a b", 1)` from "a b" and from "a b c" can be told
+ * apart.
+ */
+pub fn splitn_char_count(s: &str, sep: char, count: uint)
+    -> (~[~str], uint) {
+    split_char_inner_count(s, sep, count, true, true)
+}
+
+fn split_char_inner(s: &str, sep: char, count: uint, allow_empty: bool,
+                    allow_trailing_empty: bool) -> ~[~str] {
+    let (result, _done) = split_char_inner_count(s, sep, count, allow_empty,
+                                                 allow_trailing_empty);
+    result
+}
+
+// Splits as split_char_inner does, also counting the cuts made
+fn split_char_inner_count(s: &str, sep: char, count: uint, allow_empty: bool,
+                          allow_trailing_empty: bool) -> (~[~str], uint) {
+    let mut result = ~[];
+    let done = split_char_inner_into(s, sep, count, allow_empty,
+                                     allow_trailing_empty, &mut result);
+    (result, done)
+}
+
+// Pushes the substrings onto `result`, returning the number of cuts made
+fn split_char_inner_into(s: &str, sep: char, count: uint, allow_empty: bool,
+                         allow_trailing_empty: bool, result: &mut ~[~str])
+    -> uint {
+    if sep < 128u as char {
+        let b = sep as u8, l = len(s);
+        let mut done = 0u;
+        let mut i = 0u, start = 0u;
+        while i < l && done < count {
+            if s[i] == b {
+                if allow_empty || start < i {
+                    unsafe {
+                        result.push(raw::slice_bytes_unique(s, start, i));
+                    }
+                }
+                start = i + 1u;
+                done += 1u;
+            }
+            i += 1u;
+        }
+        // only push a non-empty trailing substring
+        if allow_trailing_empty || start < l {
+            unsafe { result.push(raw::slice_bytes_unique(s, start, l) ) };
+        }
+        done
     } else {
-        split_inner(s, |cur| cur == sep, count, allow_empty, allow_trailing_empty)
+        split_inner_into(s, |cur| cur == sep, count, allow_empty,
+                         allow_trailing_empty, result)
     }
 }
 
@@ -511,6 +1075,40 @@ pub fn splitn(s: &str,
     split_inner(s, sepfn, count, true, true)
 }
 
+/**
+ * Splits a string into substrings using a character function, scanning
+ * from the end
+ *
+ * The result is the same as `split`, in left-to-right order.
+ */
+pub fn rsplit(s: &str, sepfn: &fn(char) -> bool) -> ~[~str] {
+    rsplitn(s, sepfn, len(s))
+}
+
+/**
+ * Splits a string into substrings using a character function, cutting at
+ * most `count` times counting from the end.
+ *
+ * The substrings are returned in left-to-right order, so the first one
+ * holds whatever is left of the string after the last cut.
+ */
+pub fn rsplitn(s: &str, sepfn: &fn(char) -> bool, count: uint) -> ~[~str] {
+    let mut result = ~[];
+    let mut i = len(s), end = len(s), done = 0u;
+    while i > 0u && done < count {
+        let CharRange {ch, next: prev} = char_range_at_reverse(s, i);
+        if sepfn(ch) {
+            unsafe { result.push(raw::slice_bytes_unique(s, i, end)); }
+            end = prev;
+            done += 1u;
+        }
+        i = prev;
+    }
+    unsafe { result.push(raw::slice_bytes_unique(s, 0u, end)); }
+    vec::reverse(result);
+    result
+}
+
 /// Like `split`, but omits empty strings from the returned vector
 pub fn split_nonempty(s: &str, sepfn: &fn(char) -> bool) -> ~[~str] {
     split_inner(s, sepfn, len(s), false, false)
@@ -527,8 +1125,27 @@ pub fn split_no_trailing(s: &str, sepfn: &fn(char) -> bool) -> ~[~str] {
 
 fn split_inner(s: &str, sepfn: &fn(cc: char) -> bool, count: uint,
                allow_empty: bool, allow_trailing_empty: bool) -> ~[~str] {
+    let (result, _done) = split_inner_count(s, sepfn, count, allow_empty,
+                                            allow_trailing_empty);
+    result
+}
+
+// Splits as split_inner does, also counting the cuts made
+fn split_inner_count(s: &str, sepfn: &fn(cc: char) -> bool, count: uint,
+                     allow_empty: bool, allow_trailing_empty: bool)
+    -> (~[~str], uint) {
+    let mut result = ~[];
+    let done = split_inner_into(s, sepfn, count, allow_empty,
+                                allow_trailing_empty, &mut result);
+    (result, done)
+}
+
+// Pushes the substrings onto `result`, returning the number of cuts made
+fn split_inner_into(s: &str, sepfn: &fn(cc: char) -> bool, count: uint,
+                    allow_empty: bool, allow_trailing_empty: bool,
+                    result: &mut ~[~str]) -> uint {
     let l = len(s);
-    let mut result = ~[], i = 0u, start = 0u, done = 0u;
+    let mut i = 0u, start = 0u, done = 0u;
     while i < l && done < count {
         let CharRange {ch, next} = char_range_at(s, i);
         if sepfn(ch) {
@@ -547,7 +1164,7 @@ fn split_inner(s: &str, sepfn: &fn(cc: char) -> bool, count: uint,
             result.push(raw::slice_bytes_unique(s, start, l));
         }
     }
-    result
+    done
 }
 
 // See Issue #1932 for why this is a naive search
@@ -604,6 +1221,23 @@ pub fn split_str(s: &'a str, sep: &'b str) -> ~[~str] {
     result
 }
 
+/**
+ * Splits a string at the first occurrence of `sep`
+ *
+ * Returns the slices before and after the separator, which is itself
+ * dropped, or `none` if `sep` does not occur. An empty `sep` matches at
+ * the start of the string.
+ */
+pub fn split_once(s: &'a str, sep: &'b str) -> Option<(&'a str, &'a str)> {
+    match find_str(s, sep) {
+      Some(i) => unsafe {
+        Some((raw::slice_bytes(s, 0u, i),
+              raw::slice_bytes(s, i + len(sep), len(s))))
+      },
+      None => None
+    }
+}
+
 pub fn split_str_nonempty(s: &'a str, sep: &'b str) -> ~[~str] {
     let mut result = ~[];
     do iter_between_matches(s, sep) |from, to| {
@@ -617,8 +1251,9 @@ pub fn split_str_nonempty(s: &'a str, sep: &'b str) -> ~[~str] {
 /// Levenshtein Distance between two strings
 pub fn levdistance(s: &str, t: &str) -> uint {
 
-    let slen = s.len();
-    let tlen = t.len();
+    // The distance matrix is indexed by character, not by byte
+    let slen = char_len(s);
+    let tlen = char_len(t);
 
     if slen == 0 { return tlen; }
     if tlen == 0 { return slen; }
@@ -648,6 +1283,109 @@ pub fn levdistance(s: &str, t: &str) -> uint {
     return dcol[tlen];
 }
 
+/**
+ * Damerau-Levenshtein Distance between two strings
+ *
+ * Like `levdistance`, but a transposition of two adjacent characters
+ * counts as a single edit. This is the restricted (optimal string
+ * alignment) variant, in which no substring is edited more than once.
+ */
+pub fn damerau_levdistance(s: &str, t: &str) -> uint {
+    let sv = chars(s), tv = chars(t);
+    let slen = sv.len(), tlen = tv.len();
+
+    if slen == 0 { return tlen; }
+    if tlen == 0 { return slen; }
+
+    // Rows i - 2, i - 1 and i of the distance matrix
+    let mut prev2 = vec::from_elem(tlen + 1, 0u);
+    let mut prev = vec::from_fn(tlen + 1, |x| x);
+    let mut cur = vec::from_elem(tlen + 1, 0u);
+
+    for uint::range(1, slen + 1) |i| {
+        cur[0] = i;
+        for uint::range(1, tlen + 1) |j| {
+            let cost = if sv[i - 1] == tv[j - 1] { 0u } else { 1u };
+            let mut d = ::cmp::min(prev[j] + 1, cur[j - 1] + 1);
+            d = ::cmp::min(d, prev[j - 1] + cost);
+            if i > 1 && j > 1 && sv[i - 1] == tv[j - 2]
+                && sv[i - 2] == tv[j - 1] {
+                d = ::cmp::min(d, prev2[j - 2] + 1);
+            }
+            cur[j] = d;
+        }
+        prev2 <-> prev;
+        prev <-> cur;
+    }
+
+    return prev[tlen];
+}
+
+/**
+ * Similarity of two strings as a ratio in [0, 1]
+ *
+ * Defined as `1.0 - levdistance(s, t) / max(char_len(s), char_len(t))`,
+ * so identical strings score 1.0. Two empty strings are identical.
+ */
+pub fn similarity(s: &str, t: &str) -> float {
+    let longest = uint::max(char_len(s), char_len(t));
+    if longest == 0u { return 1.0; }
+    1.0 - (levdistance(s, t) as float) / (longest as float)
+}
+
+/**
+ * Normalized Levenshtein similarity of two strings, in [0, 1]
+ *
+ * This is `similarity`, under the name used by other string libraries.
+ */
+#[inline(always)]
+pub fn levenshtein_ratio(a: &str, b: &str) -> float {
+    similarity(a, b)
+}
+
+/**
+ * Returns the longest substring occurring at least twice in a string
+ *
+ * The occurrences may overlap. Ties are broken by the earliest start,
+ * and the empty string is returned if no character repeats.
+ */
+pub fn longest_repeated_substring(s: &'a str) -> &'a str {
+    // Characters and their byte offsets, plus the offset of the end
+    let mut cs = ~[], offs = ~[];
+    let l = len(s);
+    let mut i = 0u;
+    while i < l {
+        let CharRange {ch, next} = char_range_at(s, i);
+        cs.push(ch);
+        offs.push(i);
+        i = next;
+    }
+    offs.push(l);
+
+    // row[j] is the length of the common prefix of the suffixes starting
+    // at chars i and j; next_row holds the same for i + 1
+    let n = cs.len();
+    let mut row = vec::from_elem(n + 1, 0u);
+    let mut next_row = vec::from_elem(n + 1, 0u);
+    let mut best_start = 0u, best_len = 0u;
+    let mut i = n;
+    while i > 0u {
+        i -= 1u;
+        for uint::range(i + 1u, n) |j| {
+            row[j] = if cs[i] == cs[j] { next_row[j + 1u] + 1u } else { 0u };
+            if row[j] > 0u && row[j] >= best_len {
+                best_start = i;
+                best_len = row[j];
+            }
+        }
+        row <-> next_row;
+    }
+
+    unsafe {
+        raw::slice_bytes(s, offs[best_start], offs[best_start + best_len])
+    }
+}
+
 /**
  * Splits a string into a vector of the substrings separated by LF ('\n').
  */
@@ -670,6 +1408,139 @@ pub fn lines_any(s: &str) -> ~[~str] {
     })
 }
 
+/**
+ * Transpose a grid of cells given as lines of `col_sep`-separated cells
+ *
+ * Row `i`, column `j` of the input becomes row `j`, column `i` of the
+ * result, which is written in the same format with rows separated by
+ * '\n'.
+ *
+ * # Return value
+ *
+ * `none` if the rows do not all have the same number of cells
+ */
+pub fn transpose_grid(s: &str, col_sep: char) -> Option<~str> {
+    let rows = vec::map(lines(s), |line| split_char(*line, col_sep));
+    if rows.is_empty() { return Some(~""); }
+    let ncols = rows[0].len();
+    for rows.each |row| {
+        if row.len() != ncols { return None; }
+    }
+
+    let sep = from_char(col_sep);
+    let mut out = with_capacity(len(s));
+    for uint::range(0u, ncols) |j| {
+        if j > 0u { push_char(&mut out, '\n'); }
+        let column = vec::map(rows, |row| copy row[j]);
+        push_str(&mut out, connect(column, sep));
+    }
+    Some(out)
+}
+
+/**
+ * Remove line comments from a string
+ *
+ * On each line, everything from the first `comment` character that is not
+ * inside a `quote`-delimited span up to the end of the line is removed.
+ * Newlines are kept, so line numbers are unchanged. An unterminated quote
+ * ends with its line.
+ */
+pub fn strip_line_comments(s: &str, comment: char, quote: char) -> ~str {
+    let mut result = with_capacity(len(s));
+    let mut in_quote = false, in_comment = false;
+    for each_char(s) |c| {
+        if c == '\n' {
+            in_quote = false;
+            in_comment = false;
+        } else if in_comment {
+            loop;
+        } else if c == quote {
+            in_quote = !in_quote;
+        } else if c == comment && !in_quote {
+            in_comment = true;
+            loop;
+        }
+        push_char(&mut result, c);
+    }
+    result
+}
+
+/**
+ * Remove the common leading whitespace from every line of a string
+ *
+ * The indent removed is the longest run of spaces and tabs that starts
+ * every non-blank line. Lines holding only whitespace are kept as they
+ * are, and line terminators are preserved.
+ */
+pub fn dedent(s: &str) -> ~str {
+    let mut indent = None;
+    for each_line(s) |line| {
+        if is_empty(trim_ascii(line)) { loop; }
+        let rest = trim_left_matches(line, |c| c == ' ' || c == '\t');
+        let ws = unsafe {
+            raw::slice_bytes(line, 0u, len(line) - len(rest))
+        };
+        indent = match indent {
+          None => Some(ws),
+          Some(prev) => {
+            let mut i = 0u;
+            let n = uint::min(len(prev), len(ws));
+            while i < n && prev[i] == ws[i] { i += 1u; }
+            Some(unsafe { raw::slice_bytes(prev, 0u, i) })
+          }
+        };
+    }
+    let cut = match indent { Some(ws) => len(ws), None => 0u };
+
+    let l = len(s);
+    let mut result = with_capacity(l);
+    let mut start = 0u;
+    while start < l {
+        let end = match find_char_from(s, '\n', start) {
+            Some(i) => i + 1u,
+            None => l
+        };
+        let line = unsafe { raw::slice_bytes(s, start, end) };
+        if is_empty(trim_ascii(line)) {
+            push_str(&mut result, line);
+        } else {
+            push_str(&mut result,
+                     unsafe { raw::slice_bytes(line, cut, len(line)) });
+        }
+        start = end;
+    }
+    result
+}
+
+/**
+ * Pad a column of numbers so that their decimal separators line up
+ *
+ * Each number is padded with leading spaces so the integer parts are
+ * right-aligned and with trailing spaces so the fractional parts, which
+ * start at the first `sep`, are left-aligned. All results have the same
+ * length in characters. A number without `sep` has an empty fractional
+ * part.
+ */
+pub fn align_decimals(nums: &[~str], sep: char) -> ~[~str] {
+    // The widths, in characters, of each integer and fractional part
+    let parts = do vec::map(nums) |n| {
+        match find_char(*n, sep) {
+          Some(i) => (count_chars(*n, 0u, i), count_chars(*n, i, len(*n))),
+          None => (char_len(*n), 0u)
+        }
+    };
+    let mut int_width = 0u, frac_width = 0u;
+    for parts.each |p| {
+        let (i, f) = *p;
+        int_width = uint::max(int_width, i);
+        frac_width = uint::max(frac_width, f);
+    }
+    do vec::mapi(nums) |k, n| {
+        let (i, f) = parts[k];
+        repeat(" ", int_width - i) + *n + repeat(" ", frac_width - f)
+    }
+}
+
 /// Splits a string into a vector of the substrings separated by whitespace
 pub fn words(s: &str) -> ~[~str] {
     split_nonempty(s, char::is_whitespace)
@@ -709,6 +1580,37 @@ pub fn split_within(ss: &str, lim: uint) -> ~[~str] {
 
 
 
+/**
+ * Word-wrap a string, starting every line with a prefix
+ *
+ * Words are packed so that each line, prefix included, is at most
+ * `width` characters long. A word that does not fit even on a line of
+ * its own is placed there anyway. Lines are joined with '\n'; a string
+ * without words produces the empty string.
+ */
+pub fn wrap_with_prefix(s: &str, width: uint, prefix: &str) -> ~str {
+    let prefix_len = char_len(prefix);
+    let mut result = ~"";
+    let mut line_len = 0u, line_empty = true;
+    for words_each(s) |word| {
+        let word_len = char_len(word);
+        if !line_empty && prefix_len + line_len + 1u + word_len > width {
+            push_char(&mut result, '\n');
+            line_empty = true;
+        }
+        if line_empty {
+            push_str(&mut result, prefix);
+            line_len = word_len;
+            line_empty = false;
+        } else {
+            push_char(&mut result, ' ');
+            line_len += 1u + word_len;
+        }
+        push_str(&mut result, word);
+    }
+    result
+}
+
 /// Convert a string to lowercase. ASCII only
 pub fn to_lower(s: &str) -> ~str {
     map(s,
@@ -723,6 +1625,194 @@ pub fn to_upper(s: &str) -> ~str {
     )
 }
 
+/**
+ * Swap the case of each letter in a string. ASCII only
+ *
+ * Lowercase letters become uppercase and uppercase letters lowercase,
+ * using the same libc case tests as `to_lower` and `to_upper`. All other
+ * characters are left alone.
+ */
+pub fn swapcase(s: &str) -> ~str {
+    map(s, |c| unsafe {
+        if (c as uint) >= 128u {
+            c
+        } else if libc::islower(c as libc::c_int) != 0 {
+            (libc::toupper(c as libc::c_char)) as char
+        } else if libc::isupper(c as libc::c_int) != 0 {
+            (libc::tolower(c as libc::c_char)) as char
+        } else {
+            c
+        }
+    })
+}
+
+/**
+ * Uppercase the first character of a string and lowercase the rest
+ *
+ * Only ASCII letters change case.
+ */
+pub fn capitalize(s: &str) -> ~str {
+    let mut result = with_capacity(len(s));
+    let mut first = true;
+    for each_char(s) |c| {
+        push_char(&mut result, if first { ascii_upper_char(c) }
+                               else { ascii_lower_char(c) });
+        first = false;
+    }
+    result
+}
+
+/**
+ * Capitalize each whitespace-delimited word of a string
+ *
+ * Every character following whitespace (or starting the string) is
+ * uppercased and every other character lowercased, so the original
+ * spacing is preserved exactly. Only ASCII letters change case.
+ */
+pub fn title_case(s: &str) -> ~str {
+    let mut result = with_capacity(len(s));
+    let mut after_space = true;
+    for each_char(s) |c| {
+        push_char(&mut result, if after_space { ascii_upper_char(c) }
+                               else { ascii_lower_char(c) });
+        after_space = char::is_whitespace(c);
+    }
+    result
+}
+
+/**
+ * Convert a string to lowercase, folding only the ASCII letters A-Z
+ *
+ * All other bytes, including those of non-ASCII characters, are left
+ * untouched, independent of the current locale.
+ */
+pub fn to_ascii_lower(s: &str) -> ~str {
+    let mut result = with_capacity(len(s));
+    for each(s) |b| {
+        unsafe { raw::push_byte(&mut result, ascii_lower_byte(b)); }
+    }
+    result
+}
+
+/**
+ * Convert a string to uppercase, folding only the ASCII letters a-z
+ *
+ * All other bytes, including those of non-ASCII characters, are left
+ * untouched, independent of the current locale.
+ */
+pub fn to_ascii_upper(s: &str) -> ~str {
+    let mut result = with_capacity(len(s));
+    for each(s) |b| {
+        unsafe { raw::push_byte(&mut result, ascii_upper_byte(b)); }
+    }
+    result
+}
+
+/**
+ * Store the ASCII-lowercased form of `s` in `out`
+ *
+ * The previous contents of `out` are discarded but its buffer is reused,
+ * so computing many case-insensitive sort keys into the same buffer only
+ * allocates when a key outgrows it.
+ */
+pub fn ascii_lower_into(s: &str, out: &mut ~str) {
+    let l = len(s);
+    reserve(&mut *out, l);
+    unsafe {
+        do as_buf(*out) |obuf, _olen| {
+            let obuf: *mut u8 = ::cast::reinterpret_cast(&obuf);
+            for eachi(s) |i, b| {
+                *ptr::mut_offset(obuf, i) = ascii_lower_byte(b);
+            }
+        }
+        raw::set_len(out, l);
+    }
+}
+
+/**
+ * Convert a string to uppercase in place, folding only the ASCII letters
+ * a-z
+ *
+ * No allocation is done; non-ASCII bytes are left untouched.
+ */
+pub fn make_ascii_upper(s: &mut ~str) {
+    unsafe {
+        do as_buf(*s) |buf, len| {
+            let buf: *mut u8 = ::cast::reinterpret_cast(&buf);
+            // len counts the null terminator
+            for uint::range(0u, len - 1u) |i| {
+                let p = ptr::mut_offset(buf, i);
+                *p = ascii_upper_byte(*p);
+            }
+        }
+    }
+}
+
+/**
+ * Convert a string to lowercase in place, folding only the ASCII letters
+ * A-Z
+ *
+ * No allocation is done; non-ASCII bytes are left untouched.
+ */
+pub fn make_ascii_lower(s: &mut ~str) {
+    unsafe {
+        do as_buf(*s) |buf, len| {
+            let buf: *mut u8 = ::cast::reinterpret_cast(&buf);
+            // len counts the null terminator
+            for uint::range(0u, len - 1u) |i| {
+                let p = ptr::mut_offset(buf, i);
+                *p = ascii_lower_byte(*p);
+            }
+        }
+    }
+}
+
+/**
+ * Reverse an ASCII string in place
+ *
+ * The bytes are swapped end for end, so no allocation is done. Fails if
+ * the string is not ASCII, since reversing its bytes would break up its
+ * multibyte characters.
+ */
+pub fn reverse_ascii(s: &mut ~str) {
+    fail_unless!(is_ascii(*s));
+    unsafe {
+        do as_buf(*s) |buf, len| {
+            let buf: *mut u8 = ::cast::reinterpret_cast(&buf);
+            // len counts the null terminator
+            let mut i = 0u, j = len - 1u;
+            while i + 1u < j {
+                j -= 1u;
+                let a = ptr::mut_offset(buf, i), b = ptr::mut_offset(buf, j);
+                let t = *a;
+                *a = *b;
+                *b = t;
+                i += 1u;
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn ascii_lower_byte(b: u8) -> u8 {
+    if b >= 'A' as u8 && b <= 'Z' as u8 { b + 32u8 } else { b }
+}
+
+#[inline(always)]
+fn ascii_upper_byte(b: u8) -> u8 {
+    if b >= 'a' as u8 && b <= 'z' as u8 { b - 32u8 } else { b }
+}
+
+#[inline(always)]
+fn ascii_lower_char(c: char) -> char {
+    if (c as uint) < 128u { ascii_lower_byte(c as u8) as char } else { c }
+}
+
+#[inline(always)]
+fn ascii_upper_char(c: char) -> char {
+    if (c as uint) < 128u { ascii_upper_byte(c as u8) as char } else { c }
+}
+
 /**
  * Replace all occurrences of one string with another
  *
@@ -749,42 +1839,106 @@ pub fn replace(s: &str, from: &str, to: &str) -> ~str {
     result
 }
 
+/**
+ * Replace all occurrences of one string with text computed per match
+ *
+ * # Arguments
+ *
+ * * s - The string containing substrings to replace
+ * * from - The string to replace
+ * * f - A function given the ordinal of each match, counting from 0, and
+ *       returning its replacement
+ *
+ * # Return value
+ *
+ * The original string with the `n`th occurrence of `from` replaced with
+ * `f(n)`
+ */
+pub fn replace_with(s: &str, from: &str, f: &fn(uint) -> ~str) -> ~str {
+    let mut result = ~"", n = 0u, first = true;
+    do iter_between_matches(s, from) |start, end| {
+        if first {
+            first = false;
+        } else {
+            let r = f(n);
+            push_str(&mut result, r);
+            n += 1u;
+        }
+        push_str(&mut result, unsafe { raw::slice_bytes(s, start, end) });
+    }
+    result
+}
+
+/**
+ * Replace the text between two markers
+ *
+ * # Arguments
+ *
+ * * s - The string containing the markers
+ * * open - The marker starting the replaced span
+ * * close - The marker ending the replaced span
+ * * with - The replacement string
+ *
+ * # Return value
+ *
+ * The original string with the text between the first `open` and the
+ * first `close` after it replaced by `with`, keeping both markers, or
+ * `none` if either marker is missing
+ */
+pub fn replace_between(s: &str, open: &str, close: &str, with: &str)
+    -> Option<~str> {
+    let begin = match find_str(s, open) {
+      None => return None,
+      Some(i) => i + len(open)
+    };
+    let end = match find_str_from(s, close, begin) {
+      None => return None,
+      Some(i) => i
+    };
+    let mut result = with_capacity(len(s) - (end - begin) + len(with));
+    unsafe {
+        push_str(&mut result, raw::slice_bytes(s, 0u, begin));
+        push_str(&mut result, with);
+        push_str(&mut result, raw::slice_bytes(s, end, len(s)));
+    }
+    Some(result)
+}
+
 /*
 Section: Comparing strings
 */
 
+/**
+ * Bytewise equality of two byte slices
+ *
+ * Only the bytes within the slices are compared, so this suits the
+ * results of `to_bytes` and `as_bytes_slice` alike.
+ */
+pub fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() { return false; }
+    do vec::as_imm_buf(a) |ap, alen| {
+        do vec::as_imm_buf(b) |bp, _blen| {
+            unsafe {
+                libc::memcmp(ap as *libc::c_void,
+                             bp as *libc::c_void,
+                             alen as libc::size_t) == 0
+            }
+        }
+    }
+}
+
 /// Bytewise slice equality
 #[cfg(notest)]
 #[lang="str_eq"]
 pub fn eq_slice(a: &str, b: &str) -> bool {
-    do as_buf(a) |ap, alen| {
-        do as_buf(b) |bp, blen| {
-            if (alen != blen) { false }
-            else {
-                unsafe {
-                    libc::memcmp(ap as *libc::c_void,
-                                 bp as *libc::c_void,
-                                 (alen - 1) as libc::size_t) == 0
-                }
-            }
-        }
-    }
+    // Compare the content bytes only, whatever follows the slices
+    bytes_eq(as_bytes_slice(a), as_bytes_slice(b))
 }
 
 #[cfg(test)]
 pub fn eq_slice(a: &str, b: &str) -> bool {
-    do as_buf(a) |ap, alen| {
-        do as_buf(b) |bp, blen| {
-            if (alen != blen) { false }
-            else {
-                unsafe {
-                    libc::memcmp(ap as *libc::c_void,
-                                 bp as *libc::c_void,
-                                 (alen - 1) as libc::size_t) == 0
-                }
-            }
-        }
-    }
+    // Compare the content bytes only, whatever follows the slices
+    bytes_eq(as_bytes_slice(a), as_bytes_slice(b))
 }
 
 /// Bytewise string equality
@@ -799,33 +1953,79 @@ pub fn eq(a: &~str, b: &~str) -> bool {
     eq_slice(*a, *b)
 }
 
-fn cmp(a: &str, b: &str) -> Ordering {
-    let low = uint::min(a.len(), b.len());
+/**
+ * Returns the byte length of the longest common prefix of two strings
+ *
+ * The result always falls on a character boundary of both strings, so
+ * a shared lead byte of differing multibyte characters is not counted.
+ */
+pub fn common_prefix_len(a: &str, b: &str) -> uint {
+    let alen = len(a), blen = len(b);
+    let mut i = 0u;
+    while i < alen && i < blen {
+        let CharRange {ch: ca, next} = char_range_at(a, i);
+        if ca != char_at(b, i) { break; }
+        i = next;
+    }
+    i
+}
 
-    for uint::range(0, low) |idx| {
-        match a[idx].cmp(&b[idx]) {
-          Greater => return Greater,
-          Less => return Less,
-          Equal => ()
-        }
+/**
+ * Returns the byte length of the longest common suffix of two strings
+ *
+ * The result always falls on a character boundary of both strings, so
+ * a shared trailing byte of differing multibyte characters is not
+ * counted.
+ */
+pub fn common_suffix_len(a: &str, b: &str) -> uint {
+    let mut i = len(a), j = len(b), n = 0u;
+    while i > 0u && j > 0u {
+        let CharRange {ch: ca, next: prev_a} = char_range_at_reverse(a, i);
+        let CharRange {ch: cb, next: prev_b} = char_range_at_reverse(b, j);
+        if ca != cb { break; }
+        n += i - prev_a;
+        i = prev_a;
+        j = prev_b;
     }
+    n
+}
 
-    a.len().cmp(&b.len())
+/**
+ * Bytewise total ordering of two strings
+ *
+ * This is the ordering used by the `TotalOrd` impls, which for UTF-8 is
+ * also the order of the code points.
+ */
+pub fn total_cmp(a: &str, b: &str) -> Ordering {
+    let av = as_bytes_slice(a), bv = as_bytes_slice(b);
+    let low = uint::min(av.len(), bv.len());
+    let r = do vec::as_imm_buf(av) |ap, _alen| {
+        do vec::as_imm_buf(bv) |bp, _blen| {
+            unsafe {
+                libc::memcmp(ap as *libc::c_void,
+                             bp as *libc::c_void,
+                             low as libc::size_t)
+            }
+        }
+    };
+    if r < 0 { Less }
+    else if r > 0 { Greater }
+    else { av.len().cmp(&bv.len()) }
 }
 
 #[cfg(notest)]
 impl TotalOrd for &'self str {
-    fn cmp(&self, other: & &'self str) -> Ordering { cmp(*self, *other) }
+    fn cmp(&self, other: & &'self str) -> Ordering { total_cmp(*self, *other) }
 }
 
 #[cfg(notest)]
 impl TotalOrd for ~str {
-    fn cmp(&self, other: &~str) -> Ordering { cmp(*self, *other) }
+    fn cmp(&self, other: &~str) -> Ordering { total_cmp(*self, *other) }
 }
 
 #[cfg(notest)]
 impl TotalOrd for @str {
-    fn cmp(&self, other: &@str) -> Ordering { cmp(*self, *other) }
+    fn cmp(&self, other: &@str) -> Ordering { total_cmp(*self, *other) }
 }
 
 /// Bytewise slice less than
@@ -931,6 +2131,24 @@ impl Equiv<~str> for &'self str {
     fn equiv(&self, other: &~str) -> bool { eq_slice(*self, *other) }
 }
 
+#[cfg(notest)]
+impl Equiv<@str> for &'self str {
+    #[inline(always)]
+    fn equiv(&self, other: &@str) -> bool { eq_slice(*self, *other) }
+}
+
+#[cfg(notest)]
+impl Equiv<~str> for @str {
+    #[inline(always)]
+    fn equiv(&self, other: &~str) -> bool { eq_slice(*self, *other) }
+}
+
+#[cfg(notest)]
+impl Equiv<&'self str> for ~str {
+    #[inline(always)]
+    fn equiv(&self, other: & &'self str) -> bool { eq_slice(*self, *other) }
+}
+
 /*
 Section: Iterating through strings
 */
@@ -963,6 +2181,48 @@ pub fn map(ss: &str, ff: &fn(char) -> char) -> ~str {
     result
 }
 
+/**
+ * Replace the middle characters of a string with `mask_char`
+ *
+ * The first `keep_start` and the last `keep_end` characters are kept. If
+ * together they cover the whole string it is returned unchanged.
+ */
+pub fn mask(s: &str, keep_start: uint, keep_end: uint, mask_char: char)
+    -> ~str {
+    let n = char_len(s);
+    if keep_start + keep_end >= n { return from_slice(s); }
+    let mut result = with_capacity(len(s));
+    for s.each_chari |i, c| {
+        if i < keep_start || i >= n - keep_end {
+            push_char(&mut result, c);
+        } else {
+            push_char(&mut result, mask_char);
+        }
+    }
+    result
+}
+
+/**
+ * Collapse runs of characters that map to the same key
+ *
+ * A character is kept unless `key` maps it to the same value as the
+ * previously kept character, so each run is replaced by its first
+ * character.
+ */
+pub fn dedup_by_key(s: &str, key: &fn(char) -> uint) -> ~str {
+    let mut result = ~"";
+    let mut last = None;
+    reserve(&mut result, len(s));
+    for s.each_char |c| {
+        let k = key(c);
+        if last != Some(k) {
+            push_char(&mut result, c);
+            last = Some(k);
+        }
+    }
+    result
+}
+
 /// Iterate over the bytes in a string
 #[inline(always)]
 pub fn each(s: &str, it: &fn(u8) -> bool) {
@@ -1017,6 +2277,81 @@ pub fn each_chari(s: &str, it: &fn(uint, char) -> bool) {
     }
 }
 
+/**
+ * Iterates over the chars in a string, with a flag that is true for the
+ * last char only
+ */
+pub fn each_char_with_last(s: &str, it: &fn(char, bool) -> bool) {
+    let mut pos = 0u;
+    let l = len(s);
+    while pos < l {
+        let CharRange {ch, next} = char_range_at(s, pos);
+        pos = next;
+        if !it(ch, next == l) { break; }
+    }
+}
+
+/**
+ * Apply a function to each run of `n` consecutive chars, without
+ * allocating
+ *
+ * The windows are borrowed from `s` and slide along by one char at a
+ * time. A string with fewer than `n` chars, or an `n` of 0, yields none.
+ */
+pub fn each_char_window(s: &'a str, n: uint, f: &fn(&'a str) -> bool) {
+    if n == 0u { return; }
+    let l = len(s);
+    let mut start = 0u, end = 0u, k = 0u;
+    while k < n {
+        if end == l { return; }
+        end = char_range_at(s, end).next;
+        k += 1u;
+    }
+    loop {
+        if !f(unsafe { raw::slice_bytes(s, start, end) }) { return; }
+        if end == l { return; }
+        start = char_range_at(s, start).next;
+        end = char_range_at(s, end).next;
+    }
+}
+
+/**
+ * Reduces the chars of a string to a single value
+ *
+ * `f` is called with the value built so far, starting from `init`, and
+ * each char in turn, and returns the next value.
+ */
+pub fn fold_chars<T>(s: &str, init: T, f: &fn(T, char) -> T) -> T {
+    // The accumulator is moved in and out of an option, as it cannot be
+    // moved out of a variable captured by the each_char body
+    let mut accum = Some(init);
+    for each_char(s) |c| {
+        let a = accum.swap_unwrap();
+        accum = Some(f(a, c));
+    }
+    accum.unwrap()
+}
+
+/**
+ * Iterates over the chars in a string that may hold invalid UTF-8
+ *
+ * Wherever no valid character starts, U+FFFD REPLACEMENT CHARACTER is
+ * yielded and the iteration moves on by a single byte, so a corrupt
+ * buffer can be walked without failing.
+ */
+pub fn each_char_lossy(s: &str, it: &fn(char) -> bool) {
+    let l = len(s);
+    let mut i = 0u;
+    while i < l {
+        let (ch, next) = match char_range_at_opt(s, i) {
+            Some(CharRange {ch, next}) => (ch, next),
+            None => ('\uFFFD', i + 1u)
+        };
+        if !it(ch) { break; }
+        i = next;
+    }
+}
+
 /// Iterates over the chars in a string in reverse
 #[inline(always)]
 pub fn each_char_reverse(s: &str, it: &fn(char) -> bool) {
@@ -1039,6 +2374,90 @@ pub fn each_chari_reverse(s: &str, it: &fn(uint, char) -> bool) {
     }
 }
 
+/**
+ * Iterates over the chars in a string in reverse, with the byte offset at
+ * which each char starts
+ *
+ * The offsets can be passed straight to `slice`.
+ */
+pub fn each_char_byte_reverse(s: &str, it: &fn(uint, char) -> bool) {
+    let mut pos = len(s);
+    while pos > 0u {
+        let CharRange {ch, next} = char_range_at_reverse(s, pos);
+        pos = next;
+        if !it(pos, ch) { break; }
+    }
+}
+
+/**
+ * Apply a function to each maximal run of characters satisfying `pred`
+ *
+ * Characters not satisfying `pred` separate the runs and are skipped.
+ * The runs are borrowed from `s`.
+ */
+pub fn each_matching_run(s: &'a str, pred: &fn(char) -> bool,
+                         it: &fn(&'a str) -> bool) {
+    for each_token(s, pred) |_start, run| {
+        if !it(run) { break; }
+    }
+}
+
+/**
+ * Apply a function to each maximal run of characters satisfying `pred`,
+ * along with the byte offset at which the run starts
+ *
+ * Characters not satisfying `pred` separate the tokens and are skipped.
+ * The tokens are borrowed from `s`.
+ */
+pub fn each_token(s: &'a str, pred: &fn(char) -> bool,
+                  f: &fn(uint, &'a str) -> bool) {
+    let l = len(s);
+    let mut i = 0u, start = 0u, in_run = false;
+    while i < l {
+        let CharRange {ch, next} = char_range_at(s, i);
+        if pred(ch) {
+            if !in_run { start = i; in_run = true; }
+        } else if in_run {
+            in_run = false;
+            if !f(start, unsafe { raw::slice_bytes(s, start, i) }) { return; }
+        }
+        i = next;
+    }
+    if in_run { f(start, unsafe { raw::slice_bytes(s, start, l) }); }
+}
+
+// 0 for uppercase, 1 for lowercase, 2 for anything else
+fn case_class(c: char) -> uint {
+    if char::is_uppercase(c) { 0u }
+    else if char::is_lowercase(c) { 1u }
+    else { 2u }
+}
+
+/**
+ * Apply a function to each maximal run of characters of the same case
+ *
+ * The classes are uppercase letters, lowercase letters, and everything
+ * else. No attempt is made to keep a leading capital with the word it
+ * starts, so "fooBarBAZ" yields "foo", "B", "ar" and "BAZ".
+ */
+pub fn each_case_run(s: &'a str, it: &fn(&'a str) -> bool) {
+    let l = len(s);
+    if l == 0u { return; }
+    let mut start = 0u, i = 0u;
+    let mut class = case_class(char_at(s, 0u));
+    while i < l {
+        let CharRange {ch, next} = char_range_at(s, i);
+        let c = case_class(ch);
+        if c != class {
+            if !it(unsafe { raw::slice_bytes(s, start, i) }) { return; }
+            start = i;
+            class = c;
+        }
+        i = next;
+    }
+    it(unsafe { raw::slice_bytes(s, start, l) });
+}
+
 /// Apply a function to each substring after splitting by character
 pub fn split_char_each(ss: &str, cc: char, ff: &fn(v: &str) -> bool) {
     vec::each(split_char(ss, cc), |s| ff(*s))
@@ -1053,6 +2472,76 @@ pub fn splitn_char_each(ss: &str, sep: char, count: uint,
     vec::each(splitn_char(ss, sep, count), |s| ff(*s))
 }
 
+/**
+ * Apply a function to each substring between null bytes
+ *
+ * This is meant for buffers holding several concatenated C strings, such
+ * as `environ`. The nulls are located by scanning the raw bytes; the text
+ * between them must be valid UTF-8. As with `split_char`, a trailing null
+ * yields a final empty slice.
+ */
+pub fn split_nul(s: &'a str, it: &fn(&'a str) -> bool) {
+    let l = len(s);
+    let mut start = 0u, i = 0u;
+    while i < l {
+        if s[i] == 0u8 {
+            if !it(unsafe { raw::slice_bytes(s, start, i) }) { return; }
+            start = i + 1u;
+        }
+        i += 1u;
+    }
+    it(unsafe { raw::slice_bytes(s, start, l) });
+}
+
+/**
+ * Apply a function to each word, without allocating
+ *
+ * The words are the non-empty whitespace-delimited runs, borrowed from
+ * `s`, in the order `words` would return them.
+ */
+pub fn each_word(s: &'a str, f: &fn(&'a str) -> bool) {
+    for each_token(s, |c| !char::is_whitespace(c)) |_start, word| {
+        if !f(word) { break; }
+    }
+}
+
+/**
+ * Apply a function to each line (by '\n'), without allocating
+ *
+ * The lines are borrowed from `s` and exclude their newline. As with
+ * `lines`, a trailing empty line is omitted.
+ */
+pub fn each_line(s: &'a str, f: &fn(&'a str) -> bool) {
+    let l = len(s);
+    let mut start = 0u;
+    while start < l {
+        let end = match find_char_from(s, '\n', start) {
+            Some(i) => i,
+            None => l
+        };
+        if !f(unsafe { raw::slice_bytes(s, start, end) }) { return; }
+        start = end + 1u;
+    }
+}
+
+/**
+ * Apply a function to each line (by '\n' and/or "\r\n"), without
+ * allocating
+ *
+ * Like `each_line`, but a '\r' ending a line is stripped as well.
+ */
+pub fn each_line_any(s: &'a str, f: &fn(&'a str) -> bool) {
+    for each_line(s) |line| {
+        let l = len(line);
+        let line = if l > 0u && line[l - 1u] == '\r' as u8 {
+            unsafe { raw::slice_bytes(line, 0u, l - 1u) }
+        } else {
+            line
+        };
+        if !f(line) { break; }
+    }
+}
+
 /// Apply a function to each word
 pub fn words_each(ss: &str, ff: &fn(v: &str) -> bool) {
     vec::each(words(ss), |s| ff(*s))
@@ -1069,6 +2558,52 @@ pub fn lines_each(ss: &str, ff: &fn(v: &str) -> bool) {
 Section: Searching
 */
 
+/**
+ * A pattern that can be searched for within a string
+ *
+ * Implemented for `char`, `&str` and `&fn(char) -> bool`, so that a single
+ * `find_pat` can take any of them. The concrete `find_char`, `find_str` and
+ * `find` functions do the actual searching.
+ */
+pub trait Search {
+    /// Returns the byte index of the first match in `haystack`, if any
+    fn find_in(&self, haystack: &str) -> Option<uint>;
+}
+
+impl Search for char {
+    #[inline(always)]
+    fn find_in(&self, haystack: &str) -> Option<uint> {
+        find_char(haystack, *self)
+    }
+}
+
+impl Search for &'self str {
+    #[inline(always)]
+    fn find_in(&self, haystack: &str) -> Option<uint> {
+        find_str(haystack, *self)
+    }
+}
+
+impl Search for &'self fn(char) -> bool {
+    #[inline(always)]
+    fn find_in(&self, haystack: &str) -> Option<uint> {
+        find(haystack, *self)
+    }
+}
+
+/**
+ * Returns the byte index of the first match of `pat`, which may be a
+ * `char`, a `&str` or a `&fn(char) -> bool`
+ *
+ * This is not named `find` because `find` already takes a predicate, and
+ * a closure literal such as `|c| c == ' '` cannot be inferred when passed
+ * for a generic `P`, so existing `find` callers would stop compiling.
+ */
+#[inline(always)]
+pub fn find_pat<P: Search>(haystack: &str, pat: P) -> Option<uint> {
+    pat.find_in(haystack)
+}
+
 /**
  * Returns the byte index of the first matching character
  *
@@ -1110,6 +2645,39 @@ pub fn find_char_from(s: &str, c: char, start: uint) -> Option<uint> {
     find_char_between(s, c, start, len(s))
 }
 
+/**
+ * Returns the byte index of the first occurrence of a byte, searching from
+ * `start` to the end of the string
+ *
+ * This is a `memchr` call, so it is fast on long strings.
+ *
+ * # Failure
+ *
+ * `start` must be less than or equal to `len(s)`
+ */
+pub fn find_byte(s: &str, b: u8, start: uint) -> Option<uint> {
+    find_byte_between(s, b, start, len(s))
+}
+
+fn find_byte_between(s: &str, b: u8, start: uint, end: uint)
+    -> Option<uint> {
+    fail_unless!(start <= end);
+    fail_unless!(end <= len(s));
+    if start == end { return None; }
+    do vec::as_imm_buf(as_bytes_slice(s)) |base, _len| {
+        unsafe {
+            let p = ptr::offset(base, start);
+            let found = libc::memchr(p as *libc::c_void, b as libc::c_int,
+                                     (end - start) as libc::size_t);
+            if ptr::is_null(found) {
+                None
+            } else {
+                Some((found as uint) - (base as uint))
+            }
+        }
+    }
+}
+
 /**
  * Returns the byte index of the first matching character within a given range
  *
@@ -1134,15 +2702,7 @@ pub fn find_char_from(s: &str, c: char, start: uint) -> Option<uint> {
 pub fn find_char_between(s: &str, c: char, start: uint, end: uint)
     -> Option<uint> {
     if c < 128u as char {
-        fail_unless!(start <= end);
-        fail_unless!(end <= len(s));
-        let mut i = start;
-        let b = c as u8;
-        while i < end {
-            if s[i] == b { return Some(i); }
-            i += 1u;
-        }
-        return None;
+        find_byte_between(s, c as u8, start, end)
     } else {
         find_between(s, start, end, |x| x == c)
     }
@@ -1215,6 +2775,7 @@ pub fn rfind_char_between(s: &str, c: char, start: uint, end: uint)
     if c < 128u as char {
         fail_unless!(start >= end);
         fail_unless!(start <= len(s));
+        fail_unless!(is_char_boundary(s, start));
         let mut i = start;
         let b = c as u8;
         while i > end {
@@ -1413,6 +2974,53 @@ pub fn find_str(haystack: &'a str, needle: &'b str) -> Option<uint> {
     find_str_between(haystack, needle, 0u, len(haystack))
 }
 
+/**
+ * Returns the position of the earliest match of any of several substrings
+ *
+ * # Arguments
+ *
+ * * `haystack` - The string to search
+ * * `needles` - The strings to search for
+ *
+ * # Return value
+ *
+ * An `option` containing the byte index of the earliest match and the
+ * index in `needles` of the needle found there, or `none` if none of them
+ * occur. Where several needles match at the same position, the first of
+ * them in `needles` is reported.
+ */
+pub fn find_any_str(haystack: &str, needles: &[&str])
+  -> Option<(uint, uint)> {
+    let l = len(haystack);
+    let mut i = 0u;
+    while i <= l {
+        for vec::eachi(needles) |j, needle| {
+            if i + len(*needle) <= l && match_at(haystack, *needle, i) {
+                return Some((i, j));
+            }
+        }
+        i += 1u;
+    }
+    None
+}
+
+/**
+ * Returns the byte range of the first matching substring
+ *
+ * # Return value
+ *
+ * An `option` containing `(start, end)`, where `end` is `start` plus the
+ * length of `needle`, or `none` if there is no match. An empty needle
+ * matches at the start of the haystack.
+ */
+pub fn find_str_range(haystack: &'a str, needle: &'b str)
+  -> Option<(uint, uint)> {
+    match find_str(haystack, needle) {
+        Some(start) => Some((start, start + len(needle))),
+        None => None
+    }
+}
+
 /**
  * Returns the byte index of the first matching substring beginning
  * from a given byte offset
@@ -1460,73 +3068,243 @@ pub fn find_str_from(haystack: &'a str, needle: &'b str, start: uint)
 pub fn find_str_between(haystack: &'a str, needle: &'b str, start: uint,
                          end:uint)
   -> Option<uint> {
-    // See Issue #1932 for why this is a naive search
     fail_unless!(end <= len(haystack));
     let needle_len = len(needle);
     if needle_len == 0u { return Some(start); }
-    if needle_len > end { return None; }
+    if start > end || end - start < needle_len { return None; }
+    memmem_between(haystack, needle, start, end)
+}
+
+// The search behind find_str_between, done by libc's memmem where the
+// platform has one. Takes a non-empty needle that fits in [start, end).
+#[cfg(target_os = "linux")]
+#[cfg(target_os = "android")]
+#[cfg(target_os = "macos")]
+#[cfg(target_os = "freebsd")]
+fn memmem_between(haystack: &str, needle: &str, start: uint, end: uint)
+  -> Option<uint> {
+    let hv = as_bytes_slice(haystack), nv = as_bytes_slice(needle);
+    do vec::as_imm_buf(hv) |hp, _hlen| {
+        do vec::as_imm_buf(nv) |np, nlen| {
+            unsafe {
+                let p = libc::funcs::extra::memmem(
+                    ptr::offset(hp, start) as *libc::c_void,
+                    (end - start) as libc::size_t,
+                    np as *libc::c_void,
+                    nlen as libc::size_t);
+                if ptr::is_null(p) {
+                    None
+                } else {
+                    Some(p as uint - hp as uint)
+                }
+            }
+        }
+    }
+}
+
+// The msvcrt has no memmem, so win32 keeps the naive search
+#[cfg(target_os = "win32")]
+fn memmem_between(haystack: &str, needle: &str, start: uint, end: uint)
+  -> Option<uint> {
+    // See Issue #1932 for why this is a naive search
+    let mut i = start;
+    let e = end - len(needle);
+    while i <= e {
+        if match_at(haystack, needle, i) { return Some(i); }
+        i += 1u;
+    }
+    return None;
+}
+
+/**
+ * Apply a function to the byte index of each match of `needle`
+ *
+ * If `overlapping` is true the search resumes one byte after the start of
+ * each match, so "aa" matches "aaaa" at 0, 1 and 2; otherwise it resumes
+ * after the end of the match, giving 0 and 2.
+ *
+ * # Failure
+ *
+ * Fails if `needle` is empty
+ */
+pub fn each_match_str_mode(s: &'a str, needle: &str, overlapping: bool,
+                           it: &fn(uint) -> bool) {
+    let needle_len = len(needle), l = len(s);
+    fail_unless!(needle_len > 0u);
+    if needle_len > l { return; }
+    let step = if overlapping { 1u } else { needle_len };
+    let e = l - needle_len;
+    let mut i = 0u;
+    while i <= e {
+        if match_at(s, needle, i) {
+            if !it(i) { return; }
+            i += step;
+        } else {
+            i += 1u;
+        }
+    }
+}
+
+/**
+ * Returns the byte indices of all matches of `needle`, in increasing order
+ *
+ * Matches do not overlap: the search resumes after the end of each one.
+ * An empty `needle` has no matches.
+ */
+pub fn find_all(haystack: &str, needle: &str) -> ~[uint] {
+    let mut result = ~[];
+    if len(needle) == 0u { return result; }
+    do iter_matches(haystack, needle) |from, _to| {
+        result.push(from);
+    }
+    result
+}
+
+/**
+ * Returns true if `needle` occurs at least `n` times in `haystack`
+ *
+ * Matches are counted without overlap, as `split_str` does, and the
+ * search stops as soon as the `n`th is found. Always true for `n == 0`.
+ * An empty needle matches at every character boundary, so it occurs
+ * `char_len(haystack) + 1` times.
+ */
+pub fn contains_at_least(haystack: &str, needle: &str, n: uint) -> bool {
+    if n == 0u { return true; }
+    if is_empty(needle) { return n <= char_len(haystack) + 1u; }
+    // Not `iter_matches`: its callback cannot stop the search early
+    let mut found = 0u;
+    for each_match_str_mode(haystack, needle, false) |_i| {
+        found += 1u;
+        if found == n { break; }
+    }
+    found == n
+}
+
+/**
+ * Returns true if one string contains another
+ *
+ * # Arguments
+ *
+ * * haystack - The string to look in
+ * * needle - The string to look for
+ */
+pub fn contains(haystack: &'a str, needle: &'b str) -> bool {
+    find_str(haystack, needle).is_some()
+}
+
+/**
+ * Returns true if a string contains a char.
+ *
+ * # Arguments
+ *
+ * * haystack - The string to look in
+ * * needle - The char to look for
+ */
+pub fn contains_char(haystack: &str, needle: char) -> bool {
+    find_char(haystack, needle).is_some()
+}
+
+/**
+ * Returns true if one string starts with another
+ *
+ * # Arguments
+ *
+ * * haystack - The string to look in
+ * * needle - The string to look for
+ */
+pub fn starts_with(haystack: &'a str, needle: &'b str) -> bool {
+    let haystack_len = len(haystack), needle_len = len(needle);
+    if needle_len == 0u { true }
+    else if needle_len > haystack_len { false }
+    else { match_at(haystack, needle, 0u) }
+}
+
+/**
+ * Returns true if one string ends with another
+ *
+ * # Arguments
+ *
+ * * haystack - The string to look in
+ * * needle - The string to look for
+ */
+pub fn ends_with(haystack: &'a str, needle: &'b str) -> bool {
+    let haystack_len = len(haystack), needle_len = len(needle);
+    if needle_len == 0u { true }
+    else if needle_len > haystack_len { false }
+    else { match_at(haystack, needle, haystack_len - needle_len) }
+}
+
+/// Returns true if the first character of a string is `c`
+pub fn starts_with_char(s: &str, c: char) -> bool {
+    len(s) > 0u && char_range_at(s, 0u).ch == c
+}
+
+/// Returns true if the last character of a string is `c`
+pub fn ends_with_char(s: &str, c: char) -> bool {
+    let l = len(s);
+    l > 0u && char_range_at_reverse(s, l).ch == c
+}
 
-    let mut i = start;
-    let e = end - needle_len;
-    while i <= e {
-        if match_at(haystack, needle, i) { return Some(i); }
+// Like match_at, but folding ASCII letters to lowercase before comparing
+fn match_at_ignore_case(haystack: &'a str, needle: &'b str, at: uint) -> bool {
+    let mut i = at;
+    for each(needle) |c| {
+        if ascii_lower_byte(haystack[i]) != ascii_lower_byte(c) {
+            return false;
+        }
         i += 1u;
     }
-    return None;
+    return true;
 }
 
 /**
- * Returns true if one string contains another
+ * Returns true if one string contains another, ignoring ASCII case
  *
  * # Arguments
  *
  * * haystack - The string to look in
  * * needle - The string to look for
  */
-pub fn contains(haystack: &'a str, needle: &'b str) -> bool {
-    find_str(haystack, needle).is_some()
-}
-
-/**
- * Returns true if a string contains a char.
- *
- * # Arguments
- *
- * * haystack - The string to look in
- * * needle - The char to look for
- */
-pub fn contains_char(haystack: &str, needle: char) -> bool {
-    find_char(haystack, needle).is_some()
+pub fn contains_ignore_case(haystack: &'a str, needle: &'b str) -> bool {
+    let haystack_len = len(haystack), needle_len = len(needle);
+    if needle_len == 0u { return true; }
+    if needle_len > haystack_len { return false; }
+    for uint::range(0u, haystack_len - needle_len + 1u) |i| {
+        if match_at_ignore_case(haystack, needle, i) { return true; }
+    }
+    false
 }
 
 /**
- * Returns true if one string starts with another
+ * Returns true if one string starts with another, ignoring ASCII case
  *
  * # Arguments
  *
  * * haystack - The string to look in
  * * needle - The string to look for
  */
-pub fn starts_with(haystack: &'a str, needle: &'b str) -> bool {
+pub fn starts_with_ignore_case(haystack: &'a str, needle: &'b str) -> bool {
     let haystack_len = len(haystack), needle_len = len(needle);
     if needle_len == 0u { true }
     else if needle_len > haystack_len { false }
-    else { match_at(haystack, needle, 0u) }
+    else { match_at_ignore_case(haystack, needle, 0u) }
 }
 
 /**
- * Returns true if one string ends with another
+ * Returns true if one string ends with another, ignoring ASCII case
  *
  * # Arguments
  *
  * * haystack - The string to look in
  * * needle - The string to look for
  */
-pub fn ends_with(haystack: &'a str, needle: &'b str) -> bool {
+pub fn ends_with_ignore_case(haystack: &'a str, needle: &'b str) -> bool {
     let haystack_len = len(haystack), needle_len = len(needle);
     if needle_len == 0u { true }
     else if needle_len > haystack_len { false }
-    else { match_at(haystack, needle, haystack_len - needle_len) }
+    else {
+        match_at_ignore_case(haystack, needle, haystack_len - needle_len)
+    }
 }
 
 /*
@@ -1569,26 +3347,99 @@ pub fn len(s: &str) -> uint {
 /// Returns the number of characters that a string holds
 pub fn char_len(s: &str) -> uint { count_chars(s, 0u, len(s)) }
 
+/**
+ * Returns each distinct character of a string with the number of times it
+ * occurs
+ *
+ * The characters are listed in order of first occurrence.
+ */
+pub fn char_counts(s: &str) -> ~[(char, uint)] {
+    let mut counts: ~[(char, uint)] = ~[];
+    for each_char(s) |c| {
+        let mut found = false;
+        for uint::range(0u, counts.len()) |i| {
+            let (d, n) = counts[i];
+            if d == c {
+                counts[i] = (d, n + 1u);
+                found = true;
+                break;
+            }
+        }
+        if !found { counts.push((c, 1u)); }
+    }
+    counts
+}
+
+/**
+ * Returns the approximate number of grapheme clusters in a string
+ *
+ * A cluster is a base character followed by any number of combining
+ * diacritical marks (U+0300 to U+036F). A combining mark with no base
+ * character before it counts as a cluster of its own.
+ */
+pub fn count_graphemes(s: &str) -> uint {
+    let mut count = 0u, first = true;
+    for s.each_char |c| {
+        if first || !is_combining_mark(c) { count += 1u; }
+        first = false;
+    }
+    count
+}
+
+// The combining characters recognized by `count_graphemes`
+fn is_combining_mark(c: char) -> bool {
+    '\u0300' <= c && c <= '\u036f'
+}
+
+/**
+ * Returns the refcount of a managed string (as just before calling this)
+ *
+ * This is `sys::refcount` on the box holding the string. It can tell
+ * whether two handles share one string.
+ */
+#[inline(always)]
+pub fn refcount(s: @str) -> uint {
+    // A managed string is a pointer to its box, as any `@T` is; the handle
+    // moves into `sys::refcount`, which drops it as before
+    let b: @u8 = unsafe { cast::transmute(s) };
+    sys::refcount(b)
+}
+
 /*
 Section: Misc
 */
 
 /// Determines if a vector of bytes contains valid UTF-8
 pub fn is_utf8(v: &[const u8]) -> bool {
+    first_invalid_utf8(v).is_none()
+}
+
+/**
+ * Finds where a vector of bytes stops being valid UTF-8
+ *
+ * # Return value
+ *
+ * The byte index at which the first invalid sequence starts, so that the
+ * bytes before it are valid UTF-8, or `none` if all of `v` is valid. An
+ * invalid sequence is a byte that cannot start a character, a start byte
+ * followed by too few continuation bytes, or a character cut off by the
+ * end of the vector.
+ */
+pub fn first_invalid_utf8(v: &[const u8]) -> Option<uint> {
     let mut i = 0u;
     let total = vec::len::<u8>(v);
     while i < total {
-        let mut chsize = utf8_char_width(v[i]);
-        if chsize == 0u { return false; }
-        if i + chsize > total { return false; }
-        i += 1u;
-        while chsize > 1u {
-            if v[i] & 192u8 != tag_cont_u8 { return false; }
-            i += 1u;
-            chsize -= 1u;
+        let chsize = utf8_char_width(v[i]);
+        if chsize == 0u { return Some(i); }
+        if i + chsize > total { return Some(i); }
+        let mut j = i + 1u;
+        while j < i + chsize {
+            if v[j] & 192u8 != tag_cont_u8 { return Some(i); }
+            j += 1u;
         }
+        i = j;
     }
-    return true;
+    return None;
 }
 
 /// Determines if a vector of `u16` contains valid UTF-16
@@ -1672,6 +3523,44 @@ pub fn from_utf16(v: &[u16]) -> ~str {
     buf
 }
 
+/**
+ * Parse an integer, detecting its radix from a prefix
+ *
+ * A leading `0x` or `0X` selects hexadecimal, `0o` octal and `0b` binary;
+ * anything else is parsed as decimal. An optional `+` or `-` sign may
+ * precede the prefix.
+ *
+ * # Return value
+ *
+ * `none` if the digits following the prefix are missing or not valid in
+ * the detected radix
+ */
+pub fn parse_int_auto(s: &str) -> Option<int> {
+    let l = len(s);
+    let negative = l > 0u && s[0] == '-' as u8;
+    let mut i = 0u;
+    if l > 0u && (s[0] == '-' as u8 || s[0] == '+' as u8) { i = 1u; }
+
+    let mut radix = 10u;
+    if i + 1u < l && s[i] == '0' as u8 {
+        match s[i + 1u] as char {
+          'x' | 'X' => { radix = 16u; i += 2u; }
+          'o' => { radix = 8u; i += 2u; }
+          'b' => { radix = 2u; i += 2u; }
+          _ => ()
+        }
+    }
+
+    // The integer parser accepts its own sign, which must not follow ours
+    if i == l || s[i] == '-' as u8 || s[i] == '+' as u8 { return None; }
+    let digits = unsafe { raw::slice_bytes(s, i, l) };
+    if negative {
+        int::from_str_radix(~"-" + digits, radix)
+    } else {
+        int::from_str_radix(digits, radix)
+    }
+}
+
 pub fn with_capacity(capacity: uint) -> ~str {
     let mut buf = ~"";
     unsafe { reserve(&mut buf, capacity); }
@@ -1717,6 +3606,26 @@ pub fn count_bytes(s: &'b str, start: uint, n: uint) -> uint {
     end - start
 }
 
+/**
+ * Counts the number of bytes taken by the `n` chars in `s` starting from
+ * `start`, or `None` if fewer than `n` chars remain.
+ *
+ * Unlike `count_bytes`, this does not fail when the string is too short
+ * or when `start` is not a character boundary.
+ */
+pub fn count_bytes_opt(s: &'b str, start: uint, n: uint) -> Option<uint> {
+    let l = len(s);
+    if start > l || !is_char_boundary(s, start) { return None; }
+    let mut end = start, cnt = n;
+    while cnt > 0u {
+        if end >= l { return None; }
+        let next = char_range_at(s, end).next;
+        cnt -= 1u;
+        end = next;
+    }
+    Some(end - start)
+}
+
 /// Given a first byte, determine how many bytes are in this UTF-8 character
 pub fn utf8_char_width(b: u8) -> uint {
     let byte: uint = b as uint;
@@ -1740,6 +3649,76 @@ pub fn is_char_boundary(s: &str, index: uint) -> bool {
     return b < 128u8 || b >= 192u8;
 }
 
+/**
+ * Returns the largest character boundary that is less than or equal to
+ * `byte`
+ *
+ * # Failure
+ *
+ * Fails if `byte` is beyond the end of the string
+ */
+pub fn char_boundary_floor(s: &str, byte: uint) -> uint {
+    let l = len(s);
+    fail_unless!(byte <= l);
+    let mut i = byte;
+    while i > 0u && i < l && s[i] & 192u8 == tag_cont_u8 {
+        i -= 1u;
+    }
+    i
+}
+
+/**
+ * Returns the smallest character boundary that is greater than or equal
+ * to `byte`
+ *
+ * # Failure
+ *
+ * Fails if `byte` is beyond the end of the string
+ */
+pub fn char_boundary_ceil(s: &str, byte: uint) -> uint {
+    let l = len(s);
+    fail_unless!(byte <= l);
+    let mut i = byte;
+    while i < l && s[i] & 192u8 == tag_cont_u8 {
+        i += 1u;
+    }
+    i
+}
+
+/**
+ * Returns true if [`begin`..`end`) is a range that `slice` would accept
+ *
+ * That is, `begin <= end`, `end` is no greater than the length of the
+ * string, and both point to character boundaries.
+ */
+pub fn is_valid_range(s: &str, begin: uint, end: uint) -> bool {
+    begin <= end && end <= len(s) &&
+        is_char_boundary(s, begin) && is_char_boundary(s, end)
+}
+
+/**
+ * Returns the 1-based line and column of a byte offset
+ *
+ * The line is one more than the number of '\n's before `byte_offset`;
+ * the column is one more than the number of characters between the last
+ * of those and `byte_offset`. Fails if `byte_offset` is not a character
+ * boundary.
+ */
+pub fn line_col(s: &str, byte_offset: uint) -> (uint, uint) {
+    fail_unless!(byte_offset <= len(s));
+    fail_unless!(is_char_boundary(s, byte_offset));
+    let mut line = 1u, line_start = 0u;
+    let mut i = 0u;
+    while i < byte_offset {
+        if s[i] == '\n' as u8 {
+            line += 1u;
+            line_start = i + 1u;
+        }
+        i += 1u;
+    }
+    (line, count_chars(s, line_start, byte_offset) + 1u)
+}
+
 /**
  * Pluck a character out of a string and return the index of the next
  * character.
@@ -1811,16 +3790,121 @@ pub fn char_range_at(s: &str, i: uint) -> CharRange {
     return CharRange {ch: val as char, next: i};
 }
 
+/**
+ * Pluck a character out of a string and return the index of the next
+ * character, or `none` if there is no valid character at `i`
+ *
+ * This is the non-failing form of `char_range_at`, for decoding input
+ * that may not be valid UTF-8.
+ *
+ * # Return value
+ *
+ * `none` if `i` is out of range, is not the start of a character, or
+ * starts a sequence that is truncated or has a malformed continuation
+ * byte
+ */
+pub fn char_range_at_opt(s: &str, i: uint) -> Option<CharRange> {
+    let l = len(s);
+    if i >= l { return None; }
+    let b0 = s[i];
+    let w = utf8_char_width(b0);
+    if w == 0u || i + w > l { return None; }
+    if w == 1u { return Some(CharRange {ch: b0 as char, next: i + 1u}); }
+    let mut val = 0u;
+    let end = i + w;
+    let mut i = i + 1u;
+    while i < end {
+        let byte = s[i];
+        if byte & 192u8 != tag_cont_u8 { return None; }
+        val <<= 6u;
+        val += (byte & 63u8) as uint;
+        i += 1u;
+    }
+    // See char_range_at
+    val += ((b0 << ((w + 1u) as u8)) as uint) << ((w - 1u) * 6u - w - 1u);
+    return Some(CharRange {ch: val as char, next: i});
+}
+
 /// Plucks the `n`th character from the beginning of a string
 pub fn char_at(s: &str, i: uint) -> char {
     return char_range_at(s, i).ch;
 }
 
+/**
+ * Returns the character starting at byte offset `i`, or `none` if `i` is
+ * out of range or not on a character boundary
+ */
+pub fn char_at_opt(s: &str, i: uint) -> Option<char> {
+    match char_range_at_opt(s, i) {
+      Some(CharRange {ch, _}) => Some(ch),
+      None => None
+    }
+}
+
+/**
+ * Returns the byte at index `i`, or `none` if `i` is not less than
+ * `len(s)`
+ *
+ * The null terminator is never returned.
+ */
+pub fn byte_at(s: &str, i: uint) -> Option<u8> {
+    if i < len(s) { Some(s[i]) } else { None }
+}
+
+/**
+ * Returns the `n`th character of a string, counting in characters rather
+ * than bytes, or `none` if the string has no more than `n` characters
+ */
+pub fn nth_char_opt(s: &str, n: uint) -> Option<char> {
+    let mut i = 0u;
+    for each_char(s) |c| {
+        if i == n { return Some(c); }
+        i += 1u;
+    }
+    None
+}
+
 pub struct CharRange {
     ch: char,
     next: uint
 }
 
+/**
+ * A forward cursor over the characters of a string slice
+ *
+ * The cursor holds the byte offset of the next character, so parsers
+ * built on `char_range_at` do not have to carry the index around
+ * separately from the string.
+ */
+pub struct CharCursor {
+    s: &'self str,
+    pos: uint
+}
+
+pub impl CharCursor<'self> {
+    /// Returns the next character and advances past it
+    fn next(&mut self) -> Option<char> {
+        if self.pos >= len(self.s) { return None; }
+        let CharRange {ch, next} = char_range_at(self.s, self.pos);
+        self.pos = next;
+        Some(ch)
+    }
+
+    /// Returns the next character without advancing
+    fn peek(&self) -> Option<char> {
+        if self.pos >= len(self.s) { return None; }
+        Some(char_at(self.s, self.pos))
+    }
+
+    /// Returns the byte offset of the next character
+    fn byte_pos(&self) -> uint { self.pos }
+}
+
+/// Returns a cursor positioned at the first character of `s`
+pub fn chars_cursor(s: &'a str) -> CharCursor<'a> {
+    CharCursor { s: s, pos: 0u }
+}
+
 /**
  * Given a byte position and a str, return the previous char and its position
  *
@@ -1954,12 +4038,28 @@ pub fn as_bytes<T>(s: &const ~str, f: &fn(&~[u8]) -> T) -> T {
  */
 pub fn as_bytes_slice(s: &'a str) -> &'a [u8] {
     unsafe {
+        // A str slice's length counts the byte after its end, which is the
+        // null terminator for whole strings; the byte slice stops before it.
         let (ptr, len): (*u8, uint) = ::cast::reinterpret_cast(&s);
         let outgoing_tuple: (*u8, uint) = (ptr, len - 1);
         return ::cast::reinterpret_cast(&outgoing_tuple);
     }
 }
 
+/**
+ * Iterate over the bytes in a string, with indices, without copying.
+ *
+ * Exactly `len(s)` bytes are visited; the null terminator is not.
+ */
+pub fn bytes_iter(s: &str, f: &fn(uint, u8) -> bool) {
+    let v = as_bytes_slice(s);
+    let mut i = 0u;
+    while i < v.len() {
+        if !f(i, v[i]) { break; }
+        i += 1u;
+    }
+}
+
 /**
  * Work with the byte buffer of a string as a null-terminated C string.
  *
@@ -1969,6 +4069,11 @@ pub fn as_bytes_slice(s: &'a str) -> &'a [u8] {
  * allocate a temporary, copy the slice, null terminate it, and pass
  * that instead.
  *
+ * This is always the case for a slice taken from the middle of a larger
+ * string, since the byte after it belongs to the rest of that string.
+ * Only the bytes of the slice itself are copied; they are not re-validated
+ * as UTF-8.
+ *
  * # Example
  *
  * ~~~
@@ -1980,7 +4085,17 @@ pub fn as_c_str<T>(s: &str, f: &fn(*libc::c_char) -> T) -> T {
         // NB: len includes the trailing null.
         fail_unless!(len > 0);
         if unsafe { *(ptr::offset(buf,len-1)) != 0 } {
-            as_c_str(from_slice(s), f)
+            let mut v: ~[u8] = vec::with_capacity(len);
+            unsafe {
+                do vec::as_mut_buf(v) |vbuf, _| {
+                    ptr::copy_memory(vbuf, buf, len - 1);
+                }
+                vec::raw::set_len(&mut v, len - 1);
+            }
+            v.push(0u8);
+            do vec::as_imm_buf(v) |vbuf, _| {
+                f(vbuf as *libc::c_char)
+            }
         } else {
             f(buf as *libc::c_char)
         }
@@ -2024,7 +4139,7 @@ pub fn as_buf<T>(s: &str, f: &fn(*u8, uint) -> T) -> T {
 pub fn reserve(s: &mut ~str, n: uint) {
     unsafe {
         let v: *mut ~[u8] = cast::transmute(s);
-        vec::reserve(&mut *v, n + 1);
+        vec::reserve(&mut *v, raw::capacity_for(n));
     }
 }
 
@@ -2049,7 +4164,7 @@ pub fn reserve(s: &mut ~str, n: uint) {
  * * n - The number of bytes to reserve space for
  */
 pub fn reserve_at_least(s: &mut ~str, n: uint) {
-    reserve(s, uint::next_power_of_two(n + 1u) - 1u)
+    reserve(s, raw::amortized_capacity_for(n))
 }
 
 /**
@@ -2073,28 +4188,230 @@ pub fn escape_default(s: &str) -> ~str {
             push_str(&mut out, char::escape_default(c));
         }
     }
-    out
+    out
+}
+
+/// Escape each char in `s` with char::escape_unicode.
+pub fn escape_unicode(s: &str) -> ~str {
+    let mut out: ~str = ~"";
+    unsafe {
+        reserve_at_least(&mut out, str::len(s));
+        for s.each_char |c| {
+            push_str(&mut out, char::escape_unicode(c));
+        }
+    }
+    out
+}
+
+/**
+ * Escape a string for inclusion in HTML text or attribute values
+ *
+ * `&`, `<`, `>`, `"` and `'` are replaced by `&amp;`, `&lt;`, `&gt;`,
+ * `&quot;` and `&#39;`; all other chars are kept as they are.
+ */
+pub fn escape_html(s: &str) -> ~str {
+    let mut out: ~str = ~"";
+    reserve_at_least(&mut out, len(s));
+    for s.each_char |c| {
+        match c {
+          '&' => push_str(&mut out, "&amp;"),
+          '<' => push_str(&mut out, "&lt;"),
+          '>' => push_str(&mut out, "&gt;"),
+          '"' => push_str(&mut out, "&quot;"),
+          '\'' => push_str(&mut out, "&#39;"),
+          _ => push_char(&mut out, c)
+        }
+    }
+    out
+}
+
+/**
+ * Decode the escapes produced by `escape_default`
+ *
+ * Understands `\t`, `\r`, `\n`, `\\`, `\'`, `\"`, and the hex escapes
+ * `\xNN`, `\uNNNN` and `\UNNNNNNNN`. All other characters are copied.
+ *
+ * # Return value
+ *
+ * `none` if an escape is unknown, truncated, or names a surrogate code
+ * point or a value beyond U+10FFFF
+ */
+pub fn unescape_default(s: &str) -> Option<~str> {
+    let l = len(s);
+    let mut out = with_capacity(l);
+    let mut i = 0u;
+    while i < l {
+        let CharRange {ch, next} = char_range_at(s, i);
+        i = next;
+        if ch != '\\' {
+            push_char(&mut out, ch);
+            loop;
+        }
+        if i >= l { return None; }
+        let CharRange {ch: esc, next} = char_range_at(s, i);
+        i = next;
+        let digits = match esc {
+          't' => { push_char(&mut out, '\t'); loop; }
+          'r' => { push_char(&mut out, '\r'); loop; }
+          'n' => { push_char(&mut out, '\n'); loop; }
+          '\\' | '\'' | '"' => { push_char(&mut out, esc); loop; }
+          'x' => 2u,
+          'u' => 4u,
+          'U' => 8u,
+          _ => return None
+        };
+        if i + digits > l { return None; }
+        let mut code = 0u;
+        for uint::range(i, i + digits) |j| {
+            match char::to_digit(s[j] as char, 16u) {
+              Some(d) => code = code * 16u + d,
+              None => return None
+            }
+        }
+        if code > 0x10FFFF || (code >= 0xD800 && code <= 0xDFFF) {
+            return None;
+        }
+        push_char(&mut out, code as char);
+        i += digits;
+    }
+    Some(out)
+}
+
+/**
+ * Percent-encode a string for use in a URL
+ *
+ * ASCII alphanumerics and the ASCII bytes listed in `unreserved` are kept
+ * as they are; every other byte of the UTF-8 encoding is written as `%XX`
+ * with uppercase hex digits.
+ */
+pub fn percent_encode(s: &str, unreserved: &[u8]) -> ~str {
+    let mut out = with_capacity(len(s));
+    for each(s) |b| {
+        let c = b as char;
+        let keep = (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z')
+            || (c >= '0' && c <= '9')
+            || (b < 128u8 && vec::contains(unreserved, &b));
+        unsafe {
+            if keep {
+                raw::push_byte(&mut out, b);
+            } else {
+                raw::push_byte(&mut out, '%' as u8);
+                raw::push_byte(&mut out, upper_hex_digit(b >> 4));
+                raw::push_byte(&mut out, upper_hex_digit(b & 15u8));
+            }
+        }
+    }
+    out
+}
+
+/**
+ * Decode a percent-encoded string
+ *
+ * Each `%XX` sequence, with hex digits of either case, is replaced by the
+ * byte it encodes. The result is returned as bytes since it need not be
+ * valid UTF-8.
+ *
+ * # Return value
+ *
+ * `none` if a `%` is not followed by two hex digits
+ */
+pub fn percent_decode(s: &str) -> Option<~[u8]> {
+    let l = len(s);
+    let mut out = vec::with_capacity(l);
+    let mut i = 0u;
+    while i < l {
+        let b = s[i];
+        if b == '%' as u8 {
+            if i + 2u >= l { return None; }
+            match (char::to_digit(s[i + 1u] as char, 16u),
+                   char::to_digit(s[i + 2u] as char, 16u)) {
+              (Some(hi), Some(lo)) => out.push((hi * 16u + lo) as u8),
+              _ => return None
+            }
+            i += 3u;
+        } else {
+            out.push(b);
+            i += 1u;
+        }
+    }
+    Some(out)
 }
 
-/// Escape each char in `s` with char::escape_unicode.
-pub fn escape_unicode(s: &str) -> ~str {
-    let mut out: ~str = ~"";
-    unsafe {
-        reserve_at_least(&mut out, str::len(s));
-        for s.each_char |c| {
-            push_str(&mut out, char::escape_unicode(c));
+/**
+ * Render bytes as hex
+ *
+ * Each byte is written as two lowercase hex digits, with no separators.
+ */
+pub fn bytes_to_hex(bytes: &[u8]) -> ~str {
+    let mut out = with_capacity(bytes.len() * 2u);
+    for vec::each(bytes) |b| {
+        unsafe {
+            raw::push_byte(&mut out, lower_hex_digit(*b >> 4));
+            raw::push_byte(&mut out, lower_hex_digit(*b & 15u8));
         }
     }
     out
 }
 
+/// Render the UTF-8 bytes of a string as lowercase hex
+pub fn to_hex(s: &str) -> ~str {
+    bytes_to_hex(as_bytes_slice(s))
+}
+
+/**
+ * Parse a string of hex digit pairs, of either case, into bytes
+ *
+ * # Return value
+ *
+ * `none` if the string has odd length or holds a non-hex character
+ */
+pub fn hex_to_bytes(h: &str) -> Option<~[u8]> {
+    let l = len(h);
+    if l % 2u != 0u { return None; }
+    let mut out = vec::with_capacity(l / 2u);
+    let mut i = 0u;
+    while i < l {
+        match (char::to_digit(h[i] as char, 16u),
+               char::to_digit(h[i + 1u] as char, 16u)) {
+          (Some(hi), Some(lo)) => out.push((hi * 16u + lo) as u8),
+          _ => return None
+        }
+        i += 2u;
+    }
+    Some(out)
+}
+
+// The uppercase hex digit for a value below 16
+fn upper_hex_digit(n: u8) -> u8 {
+    if n < 10u8 { '0' as u8 + n } else { 'A' as u8 + n - 10u8 }
+}
+
+// The lowercase hex digit for a value below 16
+fn lower_hex_digit(n: u8) -> u8 {
+    if n < 10u8 { '0' as u8 + n } else { 'a' as u8 + n - 10u8 }
+}
+
+/**
+ * Render any value as a string, for diagnostics
+ *
+ * This is `sys::log_str`, so the format is the one `repr` uses.
+ */
+#[inline(always)]
+pub fn debug_str<T>(t: &T) -> ~str {
+    sys::log_str(t)
+}
+
 /// Unsafe operations
 pub mod raw {
     use cast;
     use libc;
+    use option::{None, Option, Some};
     use ptr;
     use str::raw;
-    use str::{as_buf, is_utf8, len, reserve_at_least};
+    use str::{as_buf, as_bytes_slice, capacity, is_char_boundary, is_utf8, len,
+              reserve_at_least};
+    use uint;
+    use unstable::intrinsics;
     use vec;
 
     /// Create a Rust string from a null-terminated *u8 buffer
@@ -2120,6 +4437,22 @@ pub mod raw {
         return ::cast::transmute(v);
     }
 
+    /**
+     * Create a Rust string from a *u8 buffer of the given length,
+     * returning `none` instead of failing if it is not valid UTF-8
+     */
+    pub unsafe fn from_buf_len_checked(buf: *const u8, len: uint)
+        -> Option<~str> {
+        let mut v: ~[u8] = vec::with_capacity(len + 1);
+        vec::as_mut_buf(v, |vbuf, _len| {
+            ptr::copy_memory(vbuf, buf as *u8, len)
+        });
+        vec::raw::set_len(&mut v, len);
+        if !is_utf8(v) { return None; }
+        v.push(0u8);
+        Some(::cast::transmute(v))
+    }
+
     /// Create a Rust string from a null-terminated C string
     pub unsafe fn from_c_str(c_str: *libc::c_char) -> ~str {
         from_buf(::cast::reinterpret_cast(&c_str))
@@ -2198,6 +4531,22 @@ pub mod raw {
         }
     }
 
+    /**
+     * Takes a bytewise (not UTF-8) slice from a string, or `none` if the
+     * bounds are bad.
+     *
+     * Returns the substring from [`begin`..`end`). Where `slice_bytes`
+     * would fail, because begin is greater than end or end is greater than
+     * the length of the string, `none` is returned instead. As with
+     * `slice_bytes`, the bounds need not be character boundaries.
+     */
+    #[inline]
+    pub unsafe fn slice_bytes_opt(s: &'a str, begin: uint, end: uint)
+        -> Option<&'a str> {
+        if begin > end || end > len(s) { return None; }
+        Some(slice_bytes(s, begin, end))
+    }
+
     /// Appends a byte to a string. (Not UTF-8 safe).
     pub unsafe fn push_byte(s: &mut ~str, b: u8) {
         let new_len = s.len() + 1;
@@ -2244,6 +4593,70 @@ pub mod raw {
         *null = 0u8;
     }
 
+    /**
+     * Sets the length of the string, but only if the result is valid UTF-8
+     *
+     * This is the safe counterpart of `set_len` for committing bytes written
+     * into a string's buffer, e.g. by a foreign function via `as_buf`.
+     * When growing the string, the newly visible bytes are validated; when
+     * shrinking it, `new_len` must be a character boundary. If the check
+     * fails, or `new_len` exceeds the capacity, the string keeps its old
+     * length and false is returned.
+     */
+    pub fn set_len_checked(s: &mut ~str, new_len: uint) -> bool {
+        let old_len = len(*s);
+        if new_len > capacity(s) { return false; }
+        if new_len <= old_len {
+            if !is_char_boundary(*s, new_len) { return false; }
+            unsafe { set_len(s, new_len); }
+            return true;
+        }
+        unsafe { set_len(s, new_len); }
+        let ok = is_utf8(vec::slice(as_bytes_slice(*s), old_len, new_len));
+        if !ok { unsafe { set_len(s, old_len); } }
+        ok
+    }
+
+    /**
+     * The size of the buffer `str::reserve` allocates for a string of
+     * `byte_len` bytes, counting the null terminator
+     */
+    #[inline(always)]
+    pub fn capacity_for(byte_len: uint) -> uint {
+        byte_len + 1u
+    }
+
+    /**
+     * The string capacity `str::reserve_at_least` rounds `byte_len` up to,
+     * not counting the null terminator
+     *
+     * The buffer holding it, null included, is a power of two in size.
+     */
+    #[inline(always)]
+    pub fn amortized_capacity_for(byte_len: uint) -> uint {
+        uint::next_power_of_two(byte_len + 1u) - 1u
+    }
+
+    /**
+     * The number of heap bytes a string takes beyond its capacity
+     *
+     * This is the part of `vec::raw::VecRepr` in front of the bytes (the
+     * box header and the fill and alloc counts) plus the null terminator,
+     * so a string of capacity `n` takes `n + str_buffer_overhead()` bytes,
+     * before any rounding by the allocator.
+     *
+     * The header is measured as the offset of `unboxed.data` rather than
+     * by `sys::size_of::<VecRepr>()`, which would also count the first
+     * byte, `data` itself, and the padding after it.
+     */
+    pub fn str_buffer_overhead() -> uint {
+        unsafe {
+            let repr: vec::raw::VecRepr = intrinsics::init();
+            let data = ptr::addr_of(&repr.unboxed.data) as uint;
+            data - ptr::addr_of(&repr) as uint + 1u
+        }
+    }
+
     #[test]
     fn test_from_buf_len() {
         unsafe {
@@ -2254,6 +4667,83 @@ pub mod raw {
         }
     }
 
+    #[test]
+    fn test_slice_bytes_opt() {
+        unsafe {
+            fail_unless!(slice_bytes_opt("abcdef", 1u, 4u) == Some("bcd"));
+            fail_unless!(slice_bytes_opt("abcdef", 0u, 6u) == Some("abcdef"));
+            fail_unless!(slice_bytes_opt("abcdef", 6u, 6u) == Some(""));
+            fail_unless!(slice_bytes_opt("中华", 0u, 3u) == Some("中"));
+            // reversed
+            fail_unless!(slice_bytes_opt("abcdef", 4u, 1u).is_none());
+            // out of range
+            fail_unless!(slice_bytes_opt("abcdef", 2u, 7u).is_none());
+            fail_unless!(slice_bytes_opt("", 0u, 1u).is_none());
+        }
+    }
+
+    #[test]
+    fn test_from_buf_len_checked() {
+        unsafe {
+            let a = ~[65u8, 0xe4_u8, 0xb8_u8, 0xad_u8, 65u8, 0u8];
+            let b = vec::raw::to_ptr(a);
+            fail_unless!(from_buf_len_checked(b, 5u) == Some(~"A中A"));
+            fail_unless!(from_buf_len_checked(b, 0u) == Some(~""));
+            // Cut through the middle of the multibyte char
+            fail_unless!(from_buf_len_checked(b, 2u).is_none());
+
+            let bad = ~[65u8, 0xff_u8, 65u8];
+            let b = vec::raw::to_ptr(bad);
+            fail_unless!(from_buf_len_checked(b, 3u).is_none());
+        }
+    }
+
+    #[test]
+    fn test_str_buffer_overhead() {
+        let overhead = str_buffer_overhead();
+        fail_unless!(overhead > 2u * ::sys::size_of::<uint>() + 1u);
+
+        let mut s = ~"hello";
+        reserve_at_least(&mut s, 100u);
+        unsafe {
+            let v: **vec::raw::VecRepr = cast::transmute(&s);
+            let repr: *vec::raw::VecRepr = *v;
+            let data = ptr::addr_of(&((*repr).unboxed.data)) as uint;
+            fail_unless!(data - (repr as uint) + 1u == overhead);
+            fail_unless!((*repr).unboxed.alloc == capacity(&s) + 1u);
+        }
+    }
+
+    #[test]
+    fn test_set_len_checked() {
+        let mut s = ~"ab";
+        reserve_at_least(&mut s, 8u);
+        fn write(s: &mut ~str, at: uint, bytes: &[u8]) {
+            do as_buf(*s) |buf, _len| {
+                for vec::eachi(bytes) |i, b| {
+                    unsafe { *ptr::mut_offset(buf as *mut u8, at + i) = *b; }
+                }
+            }
+        }
+
+        write(&mut s, 2u, [0xe4_u8, 0xb8_u8, 0xad_u8]);
+        fail_unless!(set_len_checked(&mut s, 5u));
+        fail_unless!(s == ~"ab中");
+
+        // A continuation byte with no lead byte
+        write(&mut s, 5u, [65u8, 0xb8_u8]);
+        fail_unless!(!set_len_checked(&mut s, 7u));
+        fail_unless!(len(s) == 5u);
+        fail_unless!(s == ~"ab中");
+
+        // Shrinking into the middle of a char is refused
+        fail_unless!(!set_len_checked(&mut s, 3u));
+        fail_unless!(set_len_checked(&mut s, 2u));
+        fail_unless!(s == ~"ab");
+
+        fail_unless!(!set_len_checked(&mut s, 1000u));
+    }
+
 }
 
 #[cfg(notest)]
@@ -2277,41 +4767,103 @@ pub trait StrSlice {
     fn any(&self, it: &fn(char) -> bool) -> bool;
     fn contains(&self, needle: &'a str) -> bool;
     fn contains_char(&self, needle: char) -> bool;
+    fn contains_at_least(&self, needle: &str, n: uint) -> bool;
+    fn find_pat<P: Search>(&self, pat: P) -> Option<uint>;
+    fn find_str_range(&self, needle: &str) -> Option<(uint, uint)>;
+    fn find_any_str(&self, needles: &[&str]) -> Option<(uint, uint)>;
+    fn find_all(&self, needle: &str) -> ~[uint];
+    fn contains_ignore_case(&self, needle: &str) -> bool;
+    fn starts_with_ignore_case(&self, needle: &str) -> bool;
+    fn ends_with_ignore_case(&self, needle: &str) -> bool;
     fn each(&self, it: &fn(u8) -> bool);
     fn eachi(&self, it: &fn(uint, u8) -> bool);
     fn each_reverse(&self, it: &fn(u8) -> bool);
     fn eachi_reverse(&self, it: &fn(uint, u8) -> bool);
     fn each_char(&self, it: &fn(char) -> bool);
     fn each_chari(&self, it: &fn(uint, char) -> bool);
+    fn each_char_with_last(&self, it: &fn(char, bool) -> bool);
+    fn each_char_lossy(&self, it: &fn(char) -> bool);
+    fn char_fold<T>(&self, init: T, f: &fn(T, char) -> T) -> T;
+    fn each_char_window(&self, n: uint, f: &fn(&'self str) -> bool);
     fn each_char_reverse(&self, it: &fn(char) -> bool);
     fn each_chari_reverse(&self, it: &fn(uint, char) -> bool);
+    fn each_char_byte_reverse(&self, it: &fn(uint, char) -> bool);
     fn ends_with(&self, needle: &str) -> bool;
+    fn ends_with_char(&self, c: char) -> bool;
     fn is_empty(&self) -> bool;
     fn is_whitespace(&self) -> bool;
     fn is_alphanumeric(&self) -> bool;
     fn len(&self) -> uint;
     fn char_len(&self) -> uint;
+    fn char_len_between(&self, begin: uint, end: uint) -> uint;
+    fn char_counts(&self) -> ~[(char, uint)];
     fn slice(&self, begin: uint, end: uint) -> &'self str;
     fn split(&self, sepfn: &fn(char) -> bool) -> ~[~str];
+    fn rsplit(&self, sepfn: &fn(char) -> bool) -> ~[~str];
+    fn rsplitn(&self, sepfn: &fn(char) -> bool, count: uint) -> ~[~str];
     fn split_char(&self, sep: char) -> ~[~str];
+    fn split_char_terminator(&self, sep: char) -> ~[~str];
+    fn split_keep_char(&self, sep: char) -> ~[~str];
     fn split_str(&self, sep: &'a str) -> ~[~str];
+    fn split_once(&self, sep: &str) -> Option<(&'self str, &'self str)>;
     fn starts_with(&self, needle: &'a str) -> bool;
+    fn starts_with_char(&self, c: char) -> bool;
     fn substr(&self, begin: uint, n: uint) -> &'self str;
+    fn substr_opt(&self, begin: uint, n: uint) -> Option<&'self str>;
+    fn slice_chars(&self, char_start: uint, char_end: uint) -> &'self str;
+    fn ellipsize(&self, max_chars: uint) -> ~str;
     fn to_lower(&self) -> ~str;
     fn to_upper(&self) -> ~str;
+    fn swapcase(&self) -> ~str;
+    fn rotate_left(&self, n: uint) -> ~str;
+    fn rotate_right(&self, n: uint) -> ~str;
+    fn capitalize(&self) -> ~str;
+    fn title_case(&self) -> ~str;
     fn escape_default(&self) -> ~str;
     fn escape_unicode(&self) -> ~str;
+    fn escape_html(&self) -> ~str;
     fn trim(&self) -> &'self str;
+    fn eq_trimmed(&self, other: &str) -> bool;
     fn trim_left(&self) -> &'self str;
     fn trim_right(&self) -> &'self str;
+    fn trim_ascii(&self) -> &'self str;
+    fn trim_ascii_left(&self) -> &'self str;
+    fn trim_ascii_right(&self) -> &'self str;
+    fn trim_newline(&self) -> &'self str;
+    fn has_bom(&self) -> bool;
+    fn strip_bom(&self) -> &'self str;
     fn trim_chars(&self, chars_to_trim: &[char]) -> &'self str;
     fn trim_left_chars(&self, chars_to_trim: &[char]) -> &'self str;
     fn trim_right_chars(&self, chars_to_trim: &[char]) -> &'self str;
+    fn trim_matches(&self, pred: &fn(char) -> bool) -> &'self str;
+    fn trim_left_matches(&self, pred: &fn(char) -> bool) -> &'self str;
+    fn trim_right_matches(&self, pred: &fn(char) -> bool) -> &'self str;
     fn to_owned(&self) -> ~str;
     fn to_managed(&self) -> @str;
     fn char_at(&self, i: uint) -> char;
     fn char_at_reverse(&self, i: uint) -> char;
+    fn char_at_opt(&self, i: uint) -> Option<char>;
+    fn byte_at(&self, i: uint) -> Option<u8>;
+    fn char_boundary_floor(&self, byte: uint) -> uint;
+    fn char_boundary_ceil(&self, byte: uint) -> uint;
+    fn is_valid_range(&self, begin: uint, end: uint) -> bool;
+    fn line_col(&self, byte_offset: uint) -> (uint, uint);
+    fn nth_char_opt(&self, n: uint) -> Option<char>;
     fn to_bytes(&self) -> ~[u8];
+    fn dedup_by_key(&self, key: &fn(char) -> uint) -> ~str;
+    fn common_prefix_len(&self, other: &str) -> uint;
+    fn common_suffix_len(&self, other: &str) -> uint;
+    fn count_graphemes(&self) -> uint;
+    fn to_ascii_lower(&self) -> ~str;
+    fn to_ascii_upper(&self) -> ~str;
+    fn each_matching_run(&self, pred: &fn(char) -> bool,
+                         it: &fn(&'self str) -> bool);
+    fn each_token(&self, pred: &fn(char) -> bool,
+                  f: &fn(uint, &'self str) -> bool);
+    fn each_case_run(&self, it: &fn(&'self str) -> bool);
+    fn each_word(&self, f: &fn(&'self str) -> bool);
+    fn each_line(&self, f: &fn(&'self str) -> bool);
+    fn each_line_any(&self, f: &fn(&'self str) -> bool);
 }
 
 /// Extension methods for strings
@@ -2338,6 +4890,50 @@ impl StrSlice for &'self str {
     fn contains_char(&self, needle: char) -> bool {
         contains_char(*self, needle)
     }
+    /**
+     * Returns the byte index of the first match of `pat`, which may be a
+     * `char`, a `&str` or a `&fn(char) -> bool`
+     */
+    #[inline]
+    fn find_pat<P: Search>(&self, pat: P) -> Option<uint> {
+        find_pat(*self, pat)
+    }
+    /// Returns the byte range of the first matching substring
+    #[inline]
+    fn find_str_range(&self, needle: &str) -> Option<(uint, uint)> {
+        find_str_range(*self, needle)
+    }
+    /**
+     * Returns the position of the earliest match of any of several
+     * substrings, with the index of the needle found there
+     */
+    #[inline]
+    fn find_any_str(&self, needles: &[&str]) -> Option<(uint, uint)> {
+        find_any_str(*self, needles)
+    }
+    /// Returns the byte indices of all matches of `needle`
+    #[inline]
+    fn find_all(&self, needle: &str) -> ~[uint] { find_all(*self, needle) }
+    /// Returns true if `needle` occurs at least `n` times
+    #[inline]
+    fn contains_at_least(&self, needle: &str, n: uint) -> bool {
+        contains_at_least(*self, needle, n)
+    }
+    /// Returns true if one string contains another, ignoring ASCII case
+    #[inline]
+    fn contains_ignore_case(&self, needle: &str) -> bool {
+        contains_ignore_case(*self, needle)
+    }
+    /// Returns true if one string starts with another, ignoring ASCII case
+    #[inline]
+    fn starts_with_ignore_case(&self, needle: &str) -> bool {
+        starts_with_ignore_case(*self, needle)
+    }
+    /// Returns true if one string ends with another, ignoring ASCII case
+    #[inline]
+    fn ends_with_ignore_case(&self, needle: &str) -> bool {
+        ends_with_ignore_case(*self, needle)
+    }
     /// Iterate over the bytes in a string
     #[inline]
     fn each(&self, it: &fn(u8) -> bool) { each(*self, it) }
@@ -2362,6 +4958,32 @@ impl StrSlice for &'self str {
     fn each_chari(&self, it: &fn(uint, char) -> bool) {
         each_chari(*self, it)
     }
+    /**
+     * Iterate over the chars in a string, with a flag that is true for the
+     * last char only
+     */
+    #[inline]
+    fn each_char_with_last(&self, it: &fn(char, bool) -> bool) {
+        each_char_with_last(*self, it)
+    }
+    /**
+     * Iterate over the chars in a string that may hold invalid UTF-8,
+     * yielding U+FFFD for each byte that starts no valid char
+     */
+    #[inline]
+    fn each_char_lossy(&self, it: &fn(char) -> bool) {
+        each_char_lossy(*self, it)
+    }
+    /// Reduces the chars of a string to a single value
+    #[inline]
+    fn char_fold<T>(&self, init: T, f: &fn(T, char) -> T) -> T {
+        fold_chars(*self, init, f)
+    }
+    /// Apply a function to each run of `n` consecutive chars
+    #[inline]
+    fn each_char_window(&self, n: uint, f: &fn(&'self str) -> bool) {
+        each_char_window(*self, n, f)
+    }
     /// Iterate over the chars in a string in reverse
     #[inline]
     fn each_char_reverse(&self, it: &fn(char) -> bool) {
@@ -2373,11 +4995,22 @@ impl StrSlice for &'self str {
     fn each_chari_reverse(&self, it: &fn(uint, char) -> bool) {
         each_chari_reverse(*self, it)
     }
+    /**
+     * Iterate over the chars in a string in reverse, with the byte offset
+     * at which each char starts
+     */
+    #[inline]
+    fn each_char_byte_reverse(&self, it: &fn(uint, char) -> bool) {
+        each_char_byte_reverse(*self, it)
+    }
     /// Returns true if one string ends with another
     #[inline]
     fn ends_with(&self, needle: &str) -> bool {
         ends_with(*self, needle)
     }
+    /// Returns true if the last character of a string is `c`
+    #[inline]
+    fn ends_with_char(&self, c: char) -> bool { ends_with_char(*self, c) }
     /// Returns true if the string has length 0
     #[inline]
     fn is_empty(&self) -> bool { is_empty(*self) }
@@ -2401,6 +5034,20 @@ impl StrSlice for &'self str {
     /// Returns the number of characters that a string holds
     #[inline]
     fn char_len(&self) -> uint { char_len(*self) }
+    /**
+     * Returns the number of characters in the byte range [`begin`..`end`),
+     * without slicing the string
+     */
+    #[inline]
+    fn char_len_between(&self, begin: uint, end: uint) -> uint {
+        count_chars(*self, begin, end)
+    }
+    /**
+     * Returns each distinct character of a string with the number of times
+     * it occurs
+     */
+    #[inline]
+    fn char_counts(&self) -> ~[(char, uint)] { char_counts(*self) }
     /**
      * Returns a slice of the given string from the byte range
      * [`begin`..`end`)
@@ -2417,22 +5064,63 @@ impl StrSlice for &'self str {
     fn split(&self, sepfn: &fn(char) -> bool) -> ~[~str] {
         split(*self, sepfn)
     }
+    /**
+     * Splits a string into substrings using a character function, scanning
+     * from the end
+     */
+    #[inline]
+    fn rsplit(&self, sepfn: &fn(char) -> bool) -> ~[~str] {
+        rsplit(*self, sepfn)
+    }
+    /**
+     * Splits a string into substrings using a character function, cutting
+     * at most `count` times counting from the end
+     */
+    #[inline]
+    fn rsplitn(&self, sepfn: &fn(char) -> bool, count: uint) -> ~[~str] {
+        rsplitn(*self, sepfn, count)
+    }
     /**
      * Splits a string into substrings at each occurrence of a given character
      */
     #[inline]
     fn split_char(&self, sep: char) -> ~[~str] { split_char(*self, sep) }
+    /**
+     * Splits a string into substrings, treating `sep` as a terminator
+     */
+    #[inline]
+    fn split_char_terminator(&self, sep: char) -> ~[~str] {
+        split_char_terminator(*self, sep)
+    }
+    /**
+     * Splits a string at each occurrence of `sep`, keeping the separators
+     * as elements of their own
+     */
+    #[inline]
+    fn split_keep_char(&self, sep: char) -> ~[~str] {
+        split_keep_char(*self, sep)
+    }
     /**
      * Splits a string into a vector of the substrings separated by a given
      * string
      */
     #[inline]
     fn split_str(&self, sep: &'a str) -> ~[~str] { split_str(*self, sep) }
+    /// Splits a string at the first occurrence of `sep`
+    #[inline]
+    fn split_once(&self, sep: &str) -> Option<(&'self str, &'self str)> {
+        split_once(*self, sep)
+    }
     /// Returns true if one string starts with another
     #[inline]
     fn starts_with(&self, needle: &'a str) -> bool {
         starts_with(*self, needle)
     }
+    /// Returns true if the first character of a string is `c`
+    #[inline]
+    fn starts_with_char(&self, c: char) -> bool {
+        starts_with_char(*self, c)
+    }
     /**
      * Take a substring of another.
      *
@@ -2443,68 +5131,264 @@ impl StrSlice for &'self str {
     fn substr(&self, begin: uint, n: uint) -> &'self str {
         substr(*self, begin, n)
     }
+    /**
+     * Take a substring of another, or `None` if fewer than `n` characters
+     * follow byte offset `begin`.
+     */
+    #[inline]
+    fn substr_opt(&self, begin: uint, n: uint) -> Option<&'self str> {
+        substr_opt(*self, begin, n)
+    }
+    /**
+     * Returns a slice of the given string from the character range
+     * [`char_start`..`char_end`)
+     */
+    #[inline]
+    fn slice_chars(&self, char_start: uint, char_end: uint) -> &'self str {
+        slice_chars(*self, char_start, char_end)
+    }
+    /**
+     * Shortens a string to at most `max_chars` characters, marking the cut
+     * with an ellipsis
+     */
+    #[inline]
+    fn ellipsize(&self, max_chars: uint) -> ~str { ellipsize(*self, max_chars) }
     /// Convert a string to lowercase
     #[inline]
     fn to_lower(&self) -> ~str { to_lower(*self) }
     /// Convert a string to uppercase
     #[inline]
     fn to_upper(&self) -> ~str { to_upper(*self) }
+    /// Swap the case of each letter in a string
+    #[inline]
+    fn swapcase(&self) -> ~str { swapcase(*self) }
+    /// Moves the first `n` characters of a string to its end
+    #[inline]
+    fn rotate_left(&self, n: uint) -> ~str { rotate_left(*self, n) }
+    /// Moves the last `n` characters of a string to its start
+    #[inline]
+    fn rotate_right(&self, n: uint) -> ~str { rotate_right(*self, n) }
+    /// Uppercase the first character and lowercase the rest
+    #[inline]
+    fn capitalize(&self) -> ~str { capitalize(*self) }
+    /// Capitalize each whitespace-delimited word
+    #[inline]
+    fn title_case(&self) -> ~str { title_case(*self) }
     /// Escape each char in `s` with char::escape_default.
     #[inline]
     fn escape_default(&self) -> ~str { escape_default(*self) }
     /// Escape each char in `s` with char::escape_unicode.
     #[inline]
     fn escape_unicode(&self) -> ~str { escape_unicode(*self) }
+    /// Escape a string for inclusion in HTML
+    #[inline]
+    fn escape_html(&self) -> ~str { escape_html(*self) }
 
     /// Returns a string with leading and trailing whitespace removed
     #[inline]
     fn trim(&self) -> &'self str { trim(*self) }
+    /**
+     * Returns true if two strings are equal once leading and trailing
+     * whitespace is removed from both
+     */
+    #[inline]
+    fn eq_trimmed(&self, other: &str) -> bool { eq_trimmed(*self, other) }
     /// Returns a string with leading whitespace removed
     #[inline]
     fn trim_left(&self) -> &'self str { trim_left(*self) }
     /// Returns a string with trailing whitespace removed
     #[inline]
     fn trim_right(&self) -> &'self str { trim_right(*self) }
+    /// Returns a string with leading and trailing ASCII whitespace removed
+    #[inline]
+    fn trim_ascii(&self) -> &'self str { trim_ascii(*self) }
+    /// Returns a string with leading ASCII whitespace removed
+    #[inline]
+    fn trim_ascii_left(&self) -> &'self str { trim_ascii_left(*self) }
+    /// Returns a string with trailing ASCII whitespace removed
+    #[inline]
+    fn trim_ascii_right(&self) -> &'self str { trim_ascii_right(*self) }
+    /// Returns a string with one trailing line ending removed
+    #[inline]
+    fn trim_newline(&self) -> &'self str { trim_newline(*self) }
+    /// Returns true if the string starts with a byte order mark
+    #[inline]
+    fn has_bom(&self) -> bool { has_bom(*self) }
+    /// Returns a string with a leading byte order mark removed
+    #[inline]
+    fn strip_bom(&self) -> &'self str { strip_bom(*self) }
+
+    #[inline]
+    fn trim_chars(&self, chars_to_trim: &[char]) -> &'self str {
+        trim_chars(*self, chars_to_trim)
+    }
+    #[inline]
+    fn trim_left_chars(&self, chars_to_trim: &[char]) -> &'self str {
+        trim_left_chars(*self, chars_to_trim)
+    }
+    #[inline]
+    fn trim_right_chars(&self, chars_to_trim: &[char]) -> &'self str {
+        trim_right_chars(*self, chars_to_trim)
+    }
+    #[inline]
+    fn trim_matches(&self, pred: &fn(char) -> bool) -> &'self str {
+        trim_matches(*self, pred)
+    }
+    #[inline]
+    fn trim_left_matches(&self, pred: &fn(char) -> bool) -> &'self str {
+        trim_left_matches(*self, pred)
+    }
+    #[inline]
+    fn trim_right_matches(&self, pred: &fn(char) -> bool) -> &'self str {
+        trim_right_matches(*self, pred)
+    }
+
+
+    #[inline]
+    fn to_owned(&self) -> ~str { from_slice(*self) }
+
+    #[inline]
+    fn to_managed(&self) -> @str {
+        let l = self.len();
+        unsafe {
+            // Copy the bytes in one go, then null terminate
+            let mut v: @[const u8] = @[];
+            at_vec::raw::reserve(&mut v, l + 1u);
+            at_vec::raw::set_len(v, l + 1u);
+            let dst = vec::raw::to_const_ptr(v) as *mut u8;
+            do as_buf(*self) |src, _len| {
+                ptr::copy_memory(dst, src, l);
+            }
+            *ptr::mut_offset(dst, l) = 0u8;
+            ::cast::transmute(v)
+        }
+    }
+
+    #[inline]
+    fn char_at(&self, i: uint) -> char { char_at(*self, i) }
+
+    #[inline]
+    fn char_at_opt(&self, i: uint) -> Option<char> { char_at_opt(*self, i) }
+
+    #[inline]
+    fn byte_at(&self, i: uint) -> Option<u8> { byte_at(*self, i) }
+
+    #[inline]
+    fn char_boundary_floor(&self, byte: uint) -> uint {
+        char_boundary_floor(*self, byte)
+    }
+
+    #[inline]
+    fn char_boundary_ceil(&self, byte: uint) -> uint {
+        char_boundary_ceil(*self, byte)
+    }
+
+    #[inline]
+    fn is_valid_range(&self, begin: uint, end: uint) -> bool {
+        is_valid_range(*self, begin, end)
+    }
+
+    /// Returns the 1-based line and column of a byte offset
+    #[inline]
+    fn line_col(&self, byte_offset: uint) -> (uint, uint) {
+        line_col(*self, byte_offset)
+    }
+
+    #[inline]
+    fn nth_char_opt(&self, n: uint) -> Option<char> {
+        nth_char_opt(*self, n)
+    }
+
+    #[inline]
+    fn char_at_reverse(&self, i: uint) -> char {
+        char_at_reverse(*self, i)
+    }
+
+    fn to_bytes(&self) -> ~[u8] { to_bytes(*self) }
+
+    /// Collapse runs of characters that map to the same key
+    #[inline]
+    fn dedup_by_key(&self, key: &fn(char) -> uint) -> ~str {
+        dedup_by_key(*self, key)
+    }
 
+    /// Returns the byte length of the longest common prefix
     #[inline]
-    fn trim_chars(&self, chars_to_trim: &[char]) -> &'self str {
-        trim_chars(*self, chars_to_trim)
+    fn common_prefix_len(&self, other: &str) -> uint {
+        common_prefix_len(*self, other)
     }
+
+    /// Returns the byte length of the longest common suffix
     #[inline]
-    fn trim_left_chars(&self, chars_to_trim: &[char]) -> &'self str {
-        trim_left_chars(*self, chars_to_trim)
+    fn common_suffix_len(&self, other: &str) -> uint {
+        common_suffix_len(*self, other)
     }
+
+    /// Returns the approximate number of grapheme clusters
     #[inline]
-    fn trim_right_chars(&self, chars_to_trim: &[char]) -> &'self str {
-        trim_right_chars(*self, chars_to_trim)
-    }
+    fn count_graphemes(&self) -> uint { count_graphemes(*self) }
 
+    /// Convert a string to lowercase, folding only ASCII letters
+    #[inline]
+    fn to_ascii_lower(&self) -> ~str { to_ascii_lower(*self) }
 
+    /// Convert a string to uppercase, folding only ASCII letters
     #[inline]
-    fn to_owned(&self) -> ~str { from_slice(*self) }
+    fn to_ascii_upper(&self) -> ~str { to_ascii_upper(*self) }
 
+    /// Apply a function to each maximal run of characters satisfying `pred`
     #[inline]
-    fn to_managed(&self) -> @str {
-        let v = at_vec::from_fn(self.len() + 1, |i| {
-            if i == self.len() { 0 } else { self[i] }
-        });
-        unsafe { ::cast::transmute(v) }
+    fn each_matching_run(&self, pred: &fn(char) -> bool,
+                         it: &fn(&'self str) -> bool) {
+        each_matching_run(*self, pred, it)
     }
 
+    /**
+     * Apply a function to each maximal run of characters satisfying
+     * `pred`, along with its starting byte offset
+     */
     #[inline]
-    fn char_at(&self, i: uint) -> char { char_at(*self, i) }
+    fn each_token(&self, pred: &fn(char) -> bool,
+                  f: &fn(uint, &'self str) -> bool) {
+        each_token(*self, pred, f)
+    }
 
+    /// Apply a function to each word, without allocating
     #[inline]
-    fn char_at_reverse(&self, i: uint) -> char {
-        char_at_reverse(*self, i)
+    fn each_word(&self, f: &fn(&'self str) -> bool) { each_word(*self, f) }
+
+    /// Apply a function to each line (by '\n'), without allocating
+    #[inline]
+    fn each_line(&self, f: &fn(&'self str) -> bool) { each_line(*self, f) }
+
+    /**
+     * Apply a function to each line (by '\n' and/or "\r\n"), without
+     * allocating
+     */
+    #[inline]
+    fn each_line_any(&self, f: &fn(&'self str) -> bool) {
+        each_line_any(*self, f)
     }
 
-    fn to_bytes(&self) -> ~[u8] { to_bytes(*self) }
+    /// Apply a function to each maximal run of characters of the same case
+    #[inline]
+    fn each_case_run(&self, it: &fn(&'self str) -> bool) {
+        each_case_run(*self, it)
+    }
 }
 
 pub trait OwnedStr {
     fn push_str(&mut self, v: &str);
     fn push_char(&mut self, c: char);
+    fn extend_with_char(&mut self, total_chars: uint, fill: char);
+    fn push_bytes_checked(&mut self, bytes: &[u8]) -> bool;
+    fn push_delimited(&mut self, sep: &str, item: &str);
+    fn repeat_into(&mut self, piece: &str, n: uint);
+    fn into_bytes(self) -> ~[u8];
+    fn truncate_chars(&mut self, max_chars: uint);
+    fn make_ascii_upper(&mut self);
+    fn make_ascii_lower(&mut self);
+    fn reverse_ascii(&mut self);
 }
 
 impl OwnedStr for ~str {
@@ -2515,6 +5399,43 @@ impl OwnedStr for ~str {
     fn push_char(&mut self, c: char) {
         push_char(self, c);
     }
+
+    fn extend_with_char(&mut self, total_chars: uint, fill: char) {
+        extend_with_char(self, total_chars, fill);
+    }
+
+    fn push_bytes_checked(&mut self, bytes: &[u8]) -> bool {
+        push_bytes_checked(self, bytes)
+    }
+
+    fn push_delimited(&mut self, sep: &str, item: &str) {
+        push_delimited(self, sep, item);
+    }
+
+    fn repeat_into(&mut self, piece: &str, n: uint) {
+        repeat_into(self, piece, n);
+    }
+
+    /// Consumes the string, returning its bytes without copying
+    fn into_bytes(self) -> ~[u8] {
+        into_bytes(self)
+    }
+
+    fn truncate_chars(&mut self, max_chars: uint) {
+        truncate_chars(self, max_chars);
+    }
+
+    fn make_ascii_upper(&mut self) {
+        make_ascii_upper(self);
+    }
+
+    fn make_ascii_lower(&mut self) {
+        make_ascii_lower(self);
+    }
+
+    fn reverse_ascii(&mut self) {
+        reverse_ascii(self);
+    }
 }
 
 impl Clone for ~str {
@@ -2533,7 +5454,7 @@ mod tests {
     use ptr;
     use str::*;
     use vec;
-    use cmp::{TotalOrd, Less, Equal, Greater};
+    use cmp::{TotalOrd, Ordering, Less, Equal, Greater, Equiv};
 
     #[test]
     fn test_eq() {
@@ -2549,6 +5470,18 @@ mod tests {
         fail_unless!((!eq_slice("foo1", "foo2")));
     }
 
+    #[test]
+    fn test_bytes_eq() {
+        fail_unless!(bytes_eq([], []));
+        fail_unless!(bytes_eq([1u8, 2u8, 3u8], [1u8, 2u8, 3u8]));
+        fail_unless!(!bytes_eq([1u8, 2u8], [1u8, 2u8, 3u8]));
+        fail_unless!(!bytes_eq([], [0u8]));
+        fail_unless!(!bytes_eq([1u8, 2u8, 3u8], [1u8, 2u8, 4u8]));
+        fail_unless!(bytes_eq(to_bytes("中华"), as_bytes_slice("中华")));
+        fail_unless!(bytes_eq(as_bytes_slice(slice("foobar", 0u, 3u)),
+                              to_bytes("foo")));
+    }
+
     #[test]
     fn test_le() {
         fail_unless!((le(&"", &"")));
@@ -2577,6 +5510,79 @@ mod tests {
         fail_unless!((char_len(~"ประเทศไทย中华Việt Nam") == 19u));
     }
 
+    #[test]
+    fn test_char_len_between() {
+        let s = "中华Việt";
+        fail_unless!(s.char_len_between(0u, len(s)) == s.char_len());
+        fail_unless!(s.char_len_between(0u, 0u) == 0u);
+        fail_unless!(s.char_len_between(3u, 7u) == 2u);
+        for [(0u, 3u), (3u, 6u), (6u, 12u), (7u, 11u), (12u, 12u)].each |r| {
+            let (a, b) = *r;
+            fail_unless!(s.char_len_between(a, b) == count_chars(s, a, b));
+            fail_unless!(s.char_len_between(a, b) == char_len(slice(s, a, b)));
+        }
+    }
+
+    #[test]
+    fn test_char_counts() {
+        let counts = char_counts("aabbbc");
+        fail_unless!(counts == ~[('a', 2u), ('b', 3u), ('c', 1u)]);
+        fail_unless!(char_counts("").len() == 0u);
+
+        let s = "ประเทศไทย中华Việt Nam";
+        let mut total = 0u;
+        for s.char_counts().each |pair| {
+            let (_c, n) = *pair;
+            total += n;
+        }
+        fail_unless!(total == char_len(s));
+    }
+
+    #[test]
+    fn test_find_byte() {
+        fail_unless!(find_byte("hello", 'l' as u8, 0u) == Some(2u));
+        fail_unless!(find_byte("hello", 'l' as u8, 3u) == Some(3u));
+        fail_unless!(find_byte("hello", 'l' as u8, 4u).is_none());
+        fail_unless!(find_byte("hello", 'o' as u8, 5u).is_none());
+        fail_unless!(find_byte("", 0u8, 0u).is_none());
+        fail_unless!(find_byte("中华", 0x8e_u8, 0u) == Some(5u));
+        // the null terminator is not part of the string
+        fail_unless!(find_byte("abc", 0u8, 0u).is_none());
+        fail_unless!(find_byte(slice("abc", 0u, 2u), 'c' as u8, 0u).is_none());
+
+        let n = 1024u * 1024u;
+        let mut big = repeat("a", n);
+        push_char(&mut big, 'z');
+        push_str(&mut big, "aaaa");
+        fail_unless!(find_byte(big, 'z' as u8, 0u) == Some(n));
+        fail_unless!(find_byte(big, 'q' as u8, 0u).is_none());
+        fail_unless!(find_char(big, 'z') == Some(n));
+        fail_unless!(find_char_between(big, 'z', 0u, n).is_none());
+    }
+
+    #[test]
+    #[should_fail]
+    #[ignore(cfg(windows))]
+    fn test_rfind_char_between_mid_char_start() {
+        // the start is inside '中'
+        rfind_char_between("a中b", 'a', 2u, 0u);
+    }
+
+    #[test]
+    #[should_fail]
+    #[ignore(cfg(windows))]
+    fn test_rfind_char_between_mid_char_start_non_ascii() {
+        rfind_char_between("华中b", '华', 4u, 0u);
+    }
+
+    #[test]
+    fn test_rfind_char_between() {
+        fail_unless!(rfind_char_between("a中b", 'a', 4u, 0u) == Some(0u));
+        fail_unless!(rfind_char_between("a中b", 'b', 5u, 0u) == Some(4u));
+        fail_unless!(rfind_char_between("a中b", 'b', 4u, 0u).is_none());
+        fail_unless!(rfind_char_between("a中b", '中', 5u, 0u) == Some(1u));
+    }
+
     #[test]
     fn test_rfind_char() {
         fail_unless!(rfind_char(~"hello", 'l') == Some(3u));
@@ -2630,6 +5636,25 @@ mod tests {
         fail_unless!(~[~"ok"] == split_char(~"ok", 'z'));
     }
 
+    #[test]
+    fn test_split_char_into() {
+        let mut out = ~[];
+        split_char_into("abc.hello.there", '.', &mut out);
+        fail_unless!(out == ~[~"abc", ~"hello", ~"there"]);
+        let cap = vec::capacity(&const out);
+
+        split_char_into("x.y", '.', &mut out);
+        fail_unless!(out == ~[~"x", ~"y"]);
+        fail_unless!(vec::capacity(&const out) == cap);
+
+        for ["...hello.there.", "", "z", "ok", "中.华."].each |s| {
+            split_char_into(*s, '.', &mut out);
+            fail_unless!(out == split_char(*s, '.'));
+        }
+        split_char_into("中华中", '华', &mut out);
+        fail_unless!(out == ~[~"中", ~"中"]);
+    }
+
     #[test]
     fn test_split_char_2() {
         let data = ~"ประเทศไทย中华Việt Nam";
@@ -2666,6 +5691,20 @@ mod tests {
         fail_unless!(~[~"w",~"x.y"] == splitn_char(~"w.x.y", '.', 1u));
     }
 
+    #[test]
+    fn test_splitn_char_count() {
+        // the limit is reached
+        fail_unless!(splitn_char_count("a b c", ' ', 1u)
+                     == (~[~"a", ~"b c"], 1u));
+        // the separators run out first
+        fail_unless!(splitn_char_count("a b", ' ', 2u) == (~[~"a", ~"b"], 1u));
+        fail_unless!(splitn_char_count("a b", ' ', 1u) == (~[~"a", ~"b"], 1u));
+        fail_unless!(splitn_char_count("abc", ' ', 3u) == (~[~"abc"], 0u));
+        fail_unless!(splitn_char_count("中华中", '华', 5u)
+                     == (~[~"中", ~"中"], 1u));
+        fail_unless!(splitn_char_count("", '.', 1u) == (~[~""], 0u));
+    }
+
     #[test]
     fn test_splitn_char_2 () {
         let data = ~"ประเทศไทย中华Việt Nam";
@@ -2690,6 +5729,34 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_split_char_terminator() {
+        fail_unless!(split_char_terminator("a.b.c.", '.')
+                     == ~[~"a", ~"b", ~"c"]);
+        fail_unless!(split_char_terminator("a.b.c", '.')
+                     == ~[~"a", ~"b", ~"c"]);
+        fail_unless!(split_char("a.b.c.", '.') == ~[~"a", ~"b", ~"c", ~""]);
+        fail_unless!(split_char_terminator("a..b", '.')
+                     == ~[~"a", ~"", ~"b"]);
+        fail_unless!(split_char_terminator(".", '.') == ~[~""]);
+        fail_unless!(split_char_terminator("", '.') == ~[]);
+        fail_unless!(split_char("", '.') == ~[~""]);
+        fail_unless!("中华中华".split_char_terminator('华') == ~[~"中", ~"中"]);
+    }
+
+    #[test]
+    fn test_split_keep_char() {
+        fail_unless!(split_keep_char("a.b", '.') == ~[~"a", ~".", ~"b"]);
+        fail_unless!(split_keep_char("...", '.') == ~[~".", ~".", ~"."]);
+        fail_unless!(split_keep_char("", '.') == ~[]);
+        fail_unless!(split_keep_char("ab", '.') == ~[~"ab"]);
+        fail_unless!("中华Việt华".split_keep_char('华')
+                     == ~[~"中", ~"华", ~"Việt", ~"华"]);
+        for ["a.b", ".a..b.", "...", "", "x", "中.华."].each |s| {
+            fail_unless!(concat(split_keep_char(*s, '.')) == s.to_owned());
+        }
+    }
+
     #[test]
     fn test_split_char_no_trailing() {
      fn t(s: &str, c: char, u: &[~str]) {
@@ -2719,6 +5786,17 @@ mod tests {
                      == split_char_no_trailing(data, 'ท'));
     }
 
+    #[test]
+    fn test_split_once() {
+        fail_unless!("Header: value".split_once(": ") == Some(("Header", "value")));
+        fail_unless!("a=b=c".split_once("=") == Some(("a", "b=c")));
+        fail_unless!("no separator".split_once(":").is_none());
+        fail_unless!("key:".split_once(":") == Some(("key", "")));
+        fail_unless!(":value".split_once(":") == Some(("", "value")));
+        fail_unless!(split_once("中华Việt", "华") == Some(("中", "Việt")));
+        fail_unless!(split_once("", ":").is_none());
+    }
+
     #[test]
     fn test_split_str() {
         fn t(s: &str, sep: &'a str, i: int, k: &str) {
@@ -2772,6 +5850,29 @@ mod tests {
         fail_unless!(~[~"ok"] == split(~"ok", |cc| cc == 'z'));
     }
 
+    #[test]
+    fn test_rsplit() {
+        let data = ~"ประเทศไทย中华Việt Nam";
+        fail_unless!(~[~"ประเทศไทย中", ~"Việt Nam"]
+                     == rsplit(data, |cc| cc == '华'));
+        fail_unless!(split(~"zzXXXzYYYz", char::is_lowercase)
+                     == rsplit(~"zzXXXzYYYz", char::is_lowercase));
+        fail_unless!(~[~"",~""] == rsplit(~"z", |cc| cc == 'z'));
+        fail_unless!(~[~""] == rsplit(~"", |cc| cc == 'z'));
+        fail_unless!(~[~"ok"] == "ok".rsplit(|cc| cc == 'z'));
+    }
+
+    #[test]
+    fn test_rsplitn() {
+        fail_unless!(rsplitn("a.b.c", |c| c == '.', 1u) == ~[~"a.b", ~"c"]);
+        fail_unless!(rsplitn("a.b.c", |c| c == '.', 2u)
+                     == ~[~"a", ~"b", ~"c"]);
+        fail_unless!(rsplitn("a.b.c", |c| c == '.', 5u)
+                     == ~[~"a", ~"b", ~"c"]);
+        fail_unless!(rsplitn("a.b.c", |c| c == '.', 0u) == ~[~"a.b.c"]);
+        fail_unless!("中.华.".rsplitn(|c| c == '.', 1u) == ~[~"中.华", ~""]);
+    }
+
     #[test]
     fn test_split_no_trailing() {
         let data = ~"ประเทศไทย中华Việt Nam";
@@ -2789,6 +5890,75 @@ mod tests {
         fail_unless!(~[~"ok"] == split_no_trailing(~"ok", |cc| cc == 'z'));
     }
 
+    #[test]
+    fn test_transpose_grid() {
+        fail_unless!(transpose_grid("a b c\nd e f", ' ')
+                     == Some(~"a d\nb e\nc f"));
+        fail_unless!(transpose_grid("a,b\nc,d\ne,f\n", ',')
+                     == Some(~"a,c,e\nb,d,f"));
+        fail_unless!(transpose_grid("中 华", ' ') == Some(~"中\n华"));
+        fail_unless!(transpose_grid("", ' ') == Some(~""));
+        fail_unless!(transpose_grid("a b c\nd e", ' ').is_none());
+    }
+
+    #[test]
+    fn test_strip_line_comments() {
+        fail_unless!(strip_line_comments("key = \"a#b\" # note", '#', '"')
+                     == ~"key = \"a#b\" ");
+        fail_unless!(strip_line_comments("# all comment", '#', '"') == ~"");
+        fail_unless!(strip_line_comments("a = 1 # x\n# y\nb = 2\n", '#', '"')
+                     == ~"a = 1 \n\nb = 2\n");
+        fail_unless!(strip_line_comments("s = '中;华' ; 注", ';', '\'')
+                     == ~"s = '中;华' ");
+        // an unterminated quote does not carry over to the next line
+        fail_unless!(strip_line_comments("a = \"x\nb # c", '#', '"')
+                     == ~"a = \"x\nb ");
+        fail_unless!(strip_line_comments("", '#', '"') == ~"");
+    }
+
+    #[test]
+    fn test_dedent() {
+        fail_unless!(dedent("    a\n      b\n    c\n") == ~"a\n  b\nc\n");
+        // a blank line in the middle is kept and does not limit the indent
+        fail_unless!(dedent("  a\n\n    b") == ~"a\n\n  b");
+        fail_unless!(dedent("\t  x\r\n  \n\t y\r\n")
+                     == ~" x\r\n  \ny\r\n");
+        // mixed tabs and spaces only share their common prefix
+        fail_unless!(dedent("\ta\n b\n") == ~"\ta\n b\n");
+        fail_unless!(dedent("  中华\n   民国") == ~"中华\n 民国");
+        fail_unless!(dedent("no indent\n  here") == ~"no indent\n  here");
+        fail_unless!(dedent("   \n") == ~"   \n");
+        fail_unless!(dedent("") == ~"");
+    }
+
+    #[test]
+    fn test_equiv() {
+        let pairs = [("abc", "abc"), ("中华", "中华"), ("abc", "abd"), ("", "a")];
+        for pairs.each |pair| {
+            let (a, b) = *pair;
+            let expected = eq_slice(a, b);
+            let owned = b.to_owned(), managed = b.to_managed();
+            fail_unless!(a.equiv(&owned) == expected);
+            fail_unless!(a.equiv(&managed) == expected);
+            fail_unless!(a.to_managed().equiv(&owned) == expected);
+            fail_unless!(a.to_owned().equiv(&b) == expected);
+        }
+    }
+
+    #[test]
+    fn test_align_decimals() {
+        let aligned = align_decimals([~"1.5", ~"12.25", ~"3"], '.');
+        fail_unless!(aligned == ~[~" 1.5 ", ~"12.25", ~" 3   "]);
+        fail_unless!(find_char(aligned[0], '.') == Some(2u));
+        fail_unless!(find_char(aligned[1], '.') == Some(2u));
+
+        fail_unless!(align_decimals([~"1,5", ~"-20"], ',')
+                     == ~[~"  1,5", ~"-20  "]);
+        fail_unless!(align_decimals([~"۱.۵", ~"10.0"], '.')
+                     == ~[~" ۱.۵", ~"10.0"]);
+        fail_unless!(align_decimals([], '.') == ~[]);
+    }
+
     #[test]
     fn test_lines() {
         let lf = ~"\nMary had a little lamb\nLittle lamb\n";
@@ -2853,6 +6023,100 @@ mod tests {
         fail_unless!(find_str(data, ~"ไท华").is_none());
     }
 
+    #[test]
+    fn test_find_any_str() {
+        fail_unless!(find_any_str("the dog", ["cat", "dog"]) == Some((4u, 1u)));
+        fail_unless!(find_any_str("cat dog", ["dog", "cat"]) == Some((0u, 1u)));
+        // ties go to the earlier needle
+        fail_unless!("abcd".find_any_str(["abc", "ab"]) == Some((0u, 0u)));
+        fail_unless!("abcd".find_any_str(["ab", "abc"]) == Some((0u, 0u)));
+        fail_unless!("ประเทศไทย中华".find_any_str(["华", "ไท"]) == Some((18u, 1u)));
+        fail_unless!(find_any_str("the dog", ["cow", "doge"]).is_none());
+        fail_unless!(find_any_str("the dog", []).is_none());
+        fail_unless!(find_any_str("", ["", "x"]) == Some((0u, 0u)));
+    }
+
+    #[test]
+    fn test_find_str_range() {
+        let data = "ประเทศไทย中华Việt Nam";
+        fail_unless!(find_str_range(data, "中华") == Some((27u, 33u)));
+        match data.find_str_range("Việt") {
+            Some((a, b)) => fail_unless!(slice(data, a, b) == "Việt"),
+            _ => fail!()
+        }
+        fail_unless!(find_str_range(data, "") == Some((0u, 0u)));
+        fail_unless!(find_str_range("", "") == Some((0u, 0u)));
+        fail_unless!(find_str_range(data, "ไท华").is_none());
+    }
+
+    #[test]
+    fn test_each_match_str_mode() {
+        let mut v = ~[];
+        for each_match_str_mode("aaaa", "aa", true) |i| { v.push(i); }
+        fail_unless!(v == ~[0u, 1u, 2u]);
+
+        let mut v = ~[];
+        for each_match_str_mode("aaaa", "aa", false) |i| { v.push(i); }
+        fail_unless!(v == ~[0u, 2u]);
+
+        let mut v = ~[];
+        for each_match_str_mode("中华中华中", "中华中", true) |i| { v.push(i); }
+        fail_unless!(v == ~[0u, 6u]);
+
+        let mut v = ~[];
+        for each_match_str_mode("abababab", "ab", false) |i| {
+            v.push(i);
+            if v.len() == 2u { break; }
+        }
+        fail_unless!(v == ~[0u, 2u]);
+
+        for each_match_str_mode("a", "aa", true) |_i| { fail!(); }
+        for each_match_str_mode("", "a", false) |_i| { fail!(); }
+    }
+
+    #[test]
+    #[should_fail]
+    #[ignore(cfg(windows))]
+    fn test_each_match_str_mode_empty_needle() {
+        for each_match_str_mode("abc", "", true) |_i| { }
+    }
+
+    #[test]
+    fn test_find_str_long_haystack() {
+        // a multi-megabyte haystack with a long needle at the very end
+        let needle = repeat("abcdefgh", 64u) + ~"!";
+        let mut haystack = repeat("abcdefg", 600000u);
+        push_str(&mut haystack, repeat("abcdefgh", 64u));
+        push_str(&mut haystack, needle);
+        let at = len(haystack) - len(needle);
+        fail_unless!(find_str(haystack, needle) == Some(at));
+        fail_unless!(contains(haystack, needle));
+        fail_unless!(find_str_between(haystack, needle, 0u, len(haystack) - 1u)
+                     .is_none());
+        fail_unless!(find_str_between(haystack, needle, at, len(haystack))
+                     == Some(at));
+        fail_unless!(!contains(haystack, "abcdefg!"));
+
+        // matches must lie wholly inside the range
+        fail_unless!(find_str_between("abcabc", "bc", 0u, 2u).is_none());
+        fail_unless!(find_str_between("abcabc", "bc", 2u, 6u) == Some(4u));
+        fail_unless!(find_str_between("abcabc", "bc", 4u, 2u).is_none());
+    }
+
+    #[bench] #[test] #[ignore(reason = "long test")]
+    fn bench_find_str_long_haystack() {
+        // the worst case for the naive search: near misses all the way
+        let needle = repeat("a", 255u) + ~"b";
+        let mut haystack = repeat("a", 4u * 1024u * 1024u);
+        push_str(&mut haystack, needle);
+        let at = len(haystack) - len(needle);
+        let mut i = 0;
+        while i < 100 {
+            fail_unless!(find_str(haystack, needle) == Some(at));
+            i += 1;
+        }
+    }
+
     #[test]
     fn test_find_str_between() {
         // byte positions
@@ -2891,6 +6155,194 @@ mod tests {
         fail_unless!("ะเทศไท" == substr("ประเทศไทย中华Việt Nam", 6u, 6u));
     }
 
+    #[test]
+    fn test_count_bytes_opt() {
+        fail_unless!(count_bytes_opt("hello", 1u, 3u) == Some(3u));
+        fail_unless!(count_bytes_opt("hello", 0u, 5u) == Some(5u));
+        fail_unless!(count_bytes_opt("hello", 5u, 0u) == Some(0u));
+        fail_unless!(count_bytes_opt("中华", 0u, 2u) == Some(6u));
+        fail_unless!(count_bytes_opt("hello", 1u, 5u).is_none());
+        fail_unless!(count_bytes_opt("中华", 3u, 2u).is_none());
+        fail_unless!(count_bytes_opt("hello", 6u, 0u).is_none());
+        // a start inside a multibyte char
+        fail_unless!(count_bytes_opt("中华", 1u, 1u).is_none());
+        fail_unless!(count_bytes_opt("中华", 4u, 0u).is_none());
+    }
+
+    #[test]
+    fn test_substr_opt() {
+        fail_unless!(substr_opt("hello", 2u, 3u) == Some("llo"));
+        fail_unless!("中华Việt".substr_opt(3u, 3u) == Some("华Vi"));
+        fail_unless!(substr_opt("hello", 2u, 4u).is_none());
+        fail_unless!("abc".substr_opt(0u, 10u).is_none());
+        fail_unless!(substr_opt("中华", 2u, 1u).is_none());
+        fail_unless!("中华Việt".substr_opt(9u, 1u).is_none());
+    }
+
+    #[test]
+    fn test_slice_chars() {
+        fail_unless!(slice_chars("中华Việt", 2u, 4u) == "Vi");
+        fail_unless!(slice_chars("中华Việt", 0u, 6u) == "中华Việt");
+        fail_unless!(slice_chars("中华Việt", 1u, 1u) == "");
+        fail_unless!(slice_chars("中华Việt", 6u, 6u) == "");
+        fail_unless!(slice_chars("", 0u, 0u) == "");
+        fail_unless!("hello".slice_chars(1u, 3u) == "el");
+    }
+
+    #[test]
+    #[should_fail]
+    #[ignore(cfg(windows))]
+    fn test_slice_chars_past_end() {
+        slice_chars("中华", 1u, 3u);
+    }
+
+    #[test]
+    #[should_fail]
+    #[ignore(cfg(windows))]
+    fn test_slice_chars_reversed() {
+        slice_chars("中华", 2u, 1u);
+    }
+
+    #[test]
+    fn test_raw_capacity_for() {
+        for [0u, 1u, 7u, 8u, 100u, 1000u].each |n| {
+            let mut s = ~"";
+            reserve(&mut s, *n);
+            fail_unless!(capacity(&s) + 1u == raw::capacity_for(*n));
+
+            let mut s = ~"";
+            reserve_at_least(&mut s, *n);
+            fail_unless!(capacity(&s) == raw::amortized_capacity_for(*n));
+        }
+        fail_unless!(raw::capacity_for(5u) == 6u);
+        fail_unless!(raw::amortized_capacity_for(5u) == 7u);
+        fail_unless!(raw::amortized_capacity_for(7u) == 7u);
+        fail_unless!(raw::amortized_capacity_for(8u) == 15u);
+    }
+
+    #[test]
+    fn test_push_bytes_checked() {
+        let mut s = ~"ab";
+        fail_unless!(push_bytes_checked(&mut s, [0xc3_u8, 0xa9_u8]));
+        fail_unless!(s == ~"abé");
+        fail_unless!(s.push_bytes_checked([]));
+        fail_unless!(s == ~"abé");
+        // A lone continuation byte
+        fail_unless!(!push_bytes_checked(&mut s, [0xa9_u8]));
+        // Half of a two-byte char
+        fail_unless!(!s.push_bytes_checked([0x63_u8, 0xc3_u8]));
+        fail_unless!(s == ~"abé");
+        fail_unless!(len(s) == 4u);
+    }
+
+    #[test]
+    fn test_push_delimited() {
+        let mut s = ~"";
+        push_delimited(&mut s, ", ", "a");
+        fail_unless!(s == ~"a");
+        push_delimited(&mut s, ", ", "b");
+        s.push_delimited(", ", "c");
+        fail_unless!(s == ~"a, b, c");
+        fail_unless!(s == connect([~"a", ~"b", ~"c"], ", "));
+
+        let mut s = ~"";
+        s.push_delimited("::", "中华");
+        s.push_delimited("::", "");
+        fail_unless!(s == ~"中华::");
+    }
+
+    #[test]
+    fn test_extend_with_char() {
+        let mut s = ~"ab";
+        extend_with_char(&mut s, 5u, '.');
+        fail_unless!(s == ~"ab...");
+
+        let mut s = ~"abcdef";
+        extend_with_char(&mut s, 5u, '.');
+        fail_unless!(s == ~"abcdef");
+
+        let mut s = ~"中";
+        s.extend_with_char(3u, '华');
+        fail_unless!(s == ~"中华华");
+        fail_unless!(capacity(&s) >= 9u);
+
+        let mut s = ~"";
+        s.extend_with_char(0u, 'x');
+        fail_unless!(s == ~"");
+    }
+
+    #[test]
+    fn test_truncate_chars() {
+        let mut s = ~"中华Việt";
+        truncate_chars(&mut s, 3u);
+        fail_unless!(s == ~"中华V");
+        fail_unless!(len(s) == 7u);
+
+        let mut s = ~"中华Việt";
+        s.truncate_chars(6u);
+        fail_unless!(s == ~"中华Việt");
+        s.truncate_chars(100u);
+        fail_unless!(s == ~"中华Việt");
+        s.truncate_chars(0u);
+        fail_unless!(s == ~"");
+    }
+
+    #[test]
+    fn test_ellipsize() {
+        let s = "ประเทศไทย中华Việt Nam";
+        fail_unless!(ellipsize(s, 19u) == ~"ประเทศไทย中华Việt Nam");
+        fail_unless!(ellipsize(s, 100u) == s.to_owned());
+        fail_unless!(ellipsize(s, 12u) == ~"ประเทศไทย中华…");
+        fail_unless!("中华Việt".ellipsize(3u) == ~"中华…");
+        fail_unless!("中华Việt".ellipsize(1u) == ~"…");
+        fail_unless!("中华Việt".ellipsize(0u) == ~"");
+        fail_unless!("".ellipsize(0u) == ~"");
+    }
+
+    #[test]
+    fn test_from_char_n() {
+        fail_unless!(spaces(4u) == ~"    ");
+        fail_unless!(spaces(0u) == ~"");
+        fail_unless!(from_char_n('-', 0u) == ~"");
+        fail_unless!(from_char_n('-', 3u) == ~"---");
+        let s = from_char_n('华', 2u);
+        fail_unless!(s == ~"华华");
+        fail_unless!(capacity(&s) == 6u);
+        let s = from_char_n('é', 3u);
+        fail_unless!(s == ~"ééé");
+        fail_unless!(capacity(&s) == 6u);
+        let s = from_char_n('\U0001D11E', 2u);
+        fail_unless!(len(s) == 8u);
+        fail_unless!(capacity(&s) == 8u);
+    }
+
+    #[test]
+    fn test_build_str() {
+        let s = build_str(|push| { push('a'); push('b'); push('c'); });
+        fail_unless!(s == ~"abc");
+
+        let data = ['x', '1', '中', '2', 'y'];
+        let s = build_str(|push| for data.each |c| {
+            if !char::is_digit(*c) { push(*c) }
+        });
+        fail_unless!(s == ~"x中y");
+
+        fail_unless!(build_str(|_push| ()) == ~"");
+    }
+
+    #[test]
+    fn test_str_writer() {
+        let mut w = StrWriter::new();
+        fail_unless!(w.len() == 0u);
+        w.write_str("a");
+        w.write_char('1');
+        w.write_str("b");
+        fail_unless!(w.len() == 3u);
+        w.write_char('中');
+        w.write_str("");
+        fail_unless!(w.unwrap() == ~"a1b中");
+    }
+
     #[test]
     fn test_concat() {
         fn t(v: &[~str], s: &str) {
@@ -2934,6 +6386,33 @@ mod tests {
         fail_unless!(repeat(~"hi", 0) == ~"");
     }
 
+    #[test]
+    fn test_repeat_into() {
+        let mut s = ~"x";
+        repeat_into(&mut s, "ab", 3u);
+        fail_unless!(s == ~"xababab");
+        fail_unless!(capacity(&s) == 7u);
+
+        let mut s = ~"ไท";
+        s.repeat_into("华", 2u);
+        fail_unless!(s == ~"ไท华华");
+        s.repeat_into("zz", 0u);
+        fail_unless!(s == ~"ไท华华");
+    }
+
+    #[test]
+    fn test_rotate() {
+        fail_unless!(rotate_left("abcde", 2u) == ~"cdeab");
+        fail_unless!(rotate_right("abcde", 2u) == ~"deabc");
+        fail_unless!(rotate_left("abcde", 0u) == ~"abcde");
+        fail_unless!(rotate_left("abcde", 7u) == ~"cdeab");
+        fail_unless!(rotate_right("abcde", 5u) == ~"abcde");
+        fail_unless!(rotate_left("", 3u) == ~"");
+        fail_unless!(rotate_right("", 3u) == ~"");
+        fail_unless!("中华Việt".rotate_left(2u) == ~"Việt中华");
+        fail_unless!("中华Việt".rotate_right(1u) == ~"t中华Việ");
+    }
+
     #[test]
     fn test_to_upper() {
         // libc::toupper, and hence str::to_upper
@@ -2956,6 +6435,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_swapcase() {
+        fail_unless!(swapcase("Hello World") == ~"hELLO wORLD");
+        fail_unless!(swapcase("") == ~"");
+        fail_unless!(swapcase("0123 ,.;:!?-_") == ~"0123 ,.;:!?-_");
+        fail_unless!("mIxEd 42".swapcase() == ~"MiXeD 42");
+        fail_unless!(swapcase(swapcase("Round Trip")) == ~"Round Trip");
+        fail_unless!(swapcase("中华 Việt") == ~"中华 vIệT");
+    }
+
+    #[test]
+    fn test_capitalize() {
+        fail_unless!(capitalize("") == ~"");
+        fail_unless!(capitalize("hello WORLD") == ~"Hello world");
+        fail_unless!(capitalize("x") == ~"X");
+        fail_unless!(capitalize(" lead") == ~" lead");
+        fail_unless!("ÉCOLE".capitalize() == ~"École");
+    }
+
+    #[test]
+    fn test_title_case() {
+        fail_unless!(title_case("hello   WORLD") == ~"Hello   World");
+        fail_unless!(title_case("") == ~"");
+        fail_unless!(title_case("  two\tWORDS\n") == ~"  Two\tWords\n");
+        fail_unless!("it's 42nd street".title_case() == ~"It's 42nd Street");
+    }
+
     #[test]
     fn test_unsafe_slice() {
         fail_unless!("ab" == unsafe {raw::slice_bytes("abc", 0, 2)});
@@ -2996,6 +6502,18 @@ mod tests {
         fail_unless!((!ends_with(~"", ~"abc")));
     }
 
+    #[test]
+    fn test_starts_ends_with_char() {
+        fail_unless!(ends_with_char("中华", '华'));
+        fail_unless!(!ends_with_char("中华", '中'));
+        fail_unless!(starts_with_char("中华", '中'));
+        fail_unless!(!starts_with_char("中华", '华'));
+        fail_unless!("a,".ends_with_char(','));
+        fail_unless!("Việt".starts_with_char('V'));
+        fail_unless!(!"".starts_with_char('a'));
+        fail_unless!(!"".ends_with_char('a'));
+    }
+
     #[test]
     fn test_is_empty() {
         fail_unless!((is_empty(~"")));
@@ -3014,6 +6532,16 @@ mod tests {
         fail_unless!(replace(~" test test ", test, ~"") == ~"   ");
     }
 
+    #[test]
+    fn test_replace_with() {
+        fail_unless!(replace_with("a,a,a", ",", |i| ::uint::to_str(i)) ==
+                     ~"a0a1a");
+        fail_unless!(replace_with("{}-{}-{}", "{}", |i| repeat("x", i + 1u))
+                     == ~"x-xx-xxx");
+        fail_unless!(replace_with("", ",", |_i| fail!()) == ~"");
+        fail_unless!(replace_with("中华", "x", |_i| fail!()) == ~"中华");
+    }
+
     #[test]
     fn test_replace_2a() {
         let data = ~"ประเทศไทย中华";
@@ -3149,6 +6677,19 @@ mod tests {
         fail_unless!(trim_chars("foo", ~['*', ' ']) == "foo");
     }
 
+    #[test]
+    fn test_trim_matches() {
+        fail_unless!(trim_left_matches("007abc", char::is_digit) == "abc");
+        fail_unless!(trim_left_matches("abc007", char::is_digit) == "abc007");
+        fail_unless!(trim_right_matches("abc007", char::is_digit) == "abc");
+        fail_unless!(trim_matches("12abc34", char::is_digit) == "abc");
+        fail_unless!(trim_matches("1234", char::is_digit) == "");
+        fail_unless!(trim_matches("", char::is_digit) == "");
+        fail_unless!("--中华--".trim_matches(|c| c == '-') == "中华");
+        fail_unless!("华中华".trim_left_matches(|c| c == '华') == "中华");
+        fail_unless!("华中华".trim_right_matches(|c| c == '华') == "华中");
+    }
+
     #[test]
     fn test_trim_left() {
         fail_unless!((trim_left("") == ""));
@@ -3179,6 +6720,57 @@ mod tests {
         fail_unless!((trim(" hey dude ") == "hey dude"));
     }
 
+    #[test]
+    fn test_trim_ascii() {
+        let s = "\u00a0 \tkey=value\r\n\u00a0";
+        fail_unless!(trim(s) == "key=value");
+        fail_unless!(trim_ascii(s) == "\u00a0 \tkey=value\r\n\u00a0");
+        fail_unless!(trim_ascii(" \t\x0b\x0ckey\r\n") == "key");
+        fail_unless!(trim_ascii_left(" \u00a0x ") == "\u00a0x ");
+        fail_unless!(trim_ascii_right(" x\u00a0 ") == " x\u00a0");
+        fail_unless!("\u3000 x \u3000".trim_ascii() == "\u3000 x \u3000");
+        fail_unless!("\u3000 x \u3000".trim() == "x");
+        fail_unless!("   ".trim_ascii() == "");
+        fail_unless!("".trim_ascii_left() == "");
+    }
+
+    #[test]
+    fn test_trim_newline() {
+        fail_unless!(trim_newline("foo\r\n") == "foo");
+        fail_unless!(trim_newline("foo\n") == "foo");
+        fail_unless!(trim_newline("foo") == "foo");
+        fail_unless!(trim_newline("foo \n") == "foo ");
+        fail_unless!("foo\n\n".trim_newline() == "foo\n");
+        fail_unless!("foo\r".trim_newline() == "foo\r");
+        fail_unless!("\r\n".trim_newline() == "");
+        fail_unless!("".trim_newline() == "");
+    }
+
+    #[test]
+    fn test_eq_trimmed() {
+        fail_unless!(eq_trimmed("  hi  ", "hi"));
+        fail_unless!(eq_trimmed("hi\n", "\thi"));
+        fail_unless!(eq_trimmed("   ", ""));
+        fail_unless!("  hi  ".eq_trimmed("hi"));
+        fail_unless!(!"h i".eq_trimmed("hi"));
+        fail_unless!(!eq_trimmed("hi", "hi!"));
+    }
+
+    #[test]
+    fn test_strip_bom() {
+        let with_bom = from_bytes(~[0xef_u8, 0xbb_u8, 0xbf_u8,
+                                    0x6b_u8, 0x3d_u8, 0x76_u8]);
+        fail_unless!(has_bom(with_bom));
+        fail_unless!(strip_bom(with_bom) == "k=v");
+        fail_unless!(!has_bom("k=v"));
+        fail_unless!(strip_bom("k=v") == "k=v");
+        fail_unless!(!has_bom(""));
+        fail_unless!(strip_bom("") == "");
+        // only the first one goes
+        fail_unless!("\uFEFF\uFEFF中".strip_bom() == "\uFEFF中");
+        fail_unless!(!"a\uFEFF".has_bom());
+    }
+
     #[test]
     fn test_is_whitespace() {
         fail_unless!((is_whitespace(~"")));
@@ -3232,13 +6824,84 @@ mod tests {
                   0x20_u8, 0x4e_u8, 0x61_u8,
                   0x6d_u8];
 
-        fail_unless!(ss == from_bytes(bb));
+        fail_unless!(ss == from_bytes(bb));
+    }
+
+    #[test]
+    #[should_fail]
+    #[ignore(cfg(windows))]
+    fn test_from_bytes_fail() {
+        let bb = ~[0xff_u8, 0xb8_u8, 0xa8_u8,
+                  0xe0_u8, 0xb9_u8, 0x84_u8,
+                  0xe0_u8, 0xb8_u8, 0x97_u8,
+                  0xe0_u8, 0xb8_u8, 0xa2_u8,
+                  0xe4_u8, 0xb8_u8, 0xad_u8,
+                  0xe5_u8, 0x8d_u8, 0x8e_u8,
+                  0x56_u8, 0x69_u8, 0xe1_u8,
+                  0xbb_u8, 0x87_u8, 0x74_u8,
+                  0x20_u8, 0x4e_u8, 0x61_u8,
+                  0x6d_u8];
+
+         let _x = from_bytes(bb);
+    }
+
+    #[test]
+    fn test_first_invalid_utf8() {
+        fail_unless!(first_invalid_utf8(~[]).is_none());
+        fail_unless!(first_invalid_utf8(to_bytes("ศไทย中华Việt Nam")).is_none());
+        // a bad continuation byte in the middle
+        fail_unless!(first_invalid_utf8(~[0x61_u8, 0x62_u8, 0xe4_u8, 0xb8_u8,
+                                          0x41_u8, 0x63_u8]) == Some(2u));
+        // a truncated final character
+        fail_unless!(first_invalid_utf8(~[0x61_u8, 0xe4_u8, 0xb8_u8])
+                     == Some(1u));
+        // a stray continuation byte
+        fail_unless!(first_invalid_utf8(~[0xe4_u8, 0xb8_u8, 0xad_u8, 0xad_u8])
+                     == Some(3u));
+        fail_unless!(first_invalid_utf8(~[0xff_u8]) == Some(0u));
+
+        fail_unless!(is_utf8(to_bytes("中华")));
+        fail_unless!(!is_utf8(~[0x61_u8, 0xe4_u8, 0xb8_u8]));
+    }
+
+    #[test]
+    fn test_from_bytes_opt() {
+        fail_unless!(from_bytes_opt(~[]) == Some(~""));
+        fail_unless!(from_bytes_opt(to_bytes("ศไทย中华")) == Some(~"ศไทย中华"));
+        fail_unless!(from_bytes_opt(~[0x61_u8, 0xff_u8]).is_none());
+        fail_unless!(from_bytes_opt(~[0xe4_u8, 0xb8_u8]).is_none());
+    }
+
+    #[test]
+    fn test_from_byte_opt() {
+        fail_unless!(from_byte_opt(65u8) == Some(~"A"));
+        fail_unless!(from_byte_opt(0u8) == Some(~"\x00"));
+        fail_unless!(from_byte_opt(127u8) == Some(~"\x7f"));
+        fail_unless!(from_byte_opt(0xC3_u8).is_none());
+        fail_unless!(from_byte_opt(128u8).is_none());
+        fail_unless!(from_byte_opt(255u8).is_none());
+    }
+
+    #[test]
+    fn test_from_bytes_owned() {
+        fail_unless!(from_bytes_owned(~[]) == Some(~""));
+        fail_unless!(from_bytes_owned(~[0x61_u8, 0xff_u8]).is_none());
+
+        let s = ~"ศไทย中华";
+        let v = into_bytes(copy s);
+        let p = unsafe { vec::raw::to_ptr(v) };
+        let t = from_bytes_owned(v).unwrap();
+        fail_unless!(t == s);
+        fail_unless!(len(t) == len(s));
+        // the buffer is reused and null-terminated
+        do as_buf(t) |buf, l| {
+            fail_unless!(buf == p);
+            fail_unless!(unsafe { *ptr::offset(buf, l - 1u) } == 0u8);
+        }
     }
 
     #[test]
-    #[should_fail]
-    #[ignore(cfg(windows))]
-    fn test_from_bytes_fail() {
+    fn test_from_bytes_lossy() {
         let bb = ~[0xff_u8, 0xb8_u8, 0xa8_u8,
                   0xe0_u8, 0xb9_u8, 0x84_u8,
                   0xe0_u8, 0xb8_u8, 0x97_u8,
@@ -3249,8 +6912,17 @@ mod tests {
                   0xbb_u8, 0x87_u8, 0x74_u8,
                   0x20_u8, 0x4e_u8, 0x61_u8,
                   0x6d_u8];
+        fail_unless!(from_bytes_lossy(bb)
+                     == ~"\uFFFD\uFFFD\uFFFDไทย中华Việt Nam");
 
-         let _x = from_bytes(bb);
+        fail_unless!(from_bytes_lossy(~[]) == ~"");
+        fail_unless!(from_bytes_lossy(to_bytes("ศไทย中华")) == ~"ศไทย中华");
+        // a truncated sequence is replaced once, not once per byte
+        fail_unless!(from_bytes_lossy(~[0x61_u8, 0xe4_u8, 0xb8_u8, 0x62_u8])
+                     == ~"a\uFFFDb");
+        fail_unless!(from_bytes_lossy(~[0xe4_u8, 0xb8_u8]) == ~"\uFFFD");
+        fail_unless!(from_bytes_lossy(~[0xe4_u8, 0xe4_u8, 0xb8_u8, 0xad_u8])
+                     == ~"\uFFFD中");
     }
 
     #[test]
@@ -3271,6 +6943,34 @@ mod tests {
         as_bytes::<()>(&~"", |_bytes| fail!() );
     }
 
+    #[test]
+    fn test_into_bytes() {
+        fail_unless!((~"abc").into_bytes() == ~[97u8, 98u8, 99u8]);
+        fail_unless!((~"").into_bytes() == ~[]);
+        fail_unless!(into_bytes(~"中华") == to_bytes("中华"));
+
+        // the buffer is reused, not copied
+        let s = ~"hello";
+        let p = do as_buf(s) |buf, _len| { buf };
+        let v = s.into_bytes();
+        fail_unless!(v.len() == 5u);
+        fail_unless!(unsafe { vec::raw::to_ptr(v) } == p);
+    }
+
+    #[test]
+    fn test_to_bytes_with_nul() {
+        fail_unless!(to_bytes_with_nul("ab") == ~[97u8, 98u8, 0u8]);
+        fail_unless!(to_bytes_with_nul("") == ~[0u8]);
+        fail_unless!(to_bytes_with_nul("中") == ~[0xe4_u8, 0xb8_u8, 0xad_u8, 0u8]);
+    }
+
+    #[test]
+    #[should_fail]
+    #[ignore(cfg(windows))]
+    fn test_to_bytes_with_nul_interior_null() {
+        let _x = to_bytes_with_nul("a\x00b");
+    }
+
     #[test]
     fn test_as_buf() {
         let a = ~"Abcdefg";
@@ -3315,6 +7015,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_as_c_str_sub_slice() {
+        let sub = unsafe { raw::slice_bytes("abcdef", 1, 4) };
+        let c = as_c_str(sub, |p| unsafe { raw::from_c_str(p) });
+        fail_unless!(c == ~"bcd");
+        fail_unless!(len(c) == 3u);
+    }
+
     #[test]
     fn vec_str_conversions() {
         let s1: ~str = ~"All mimsy were the borogoves";
@@ -3352,6 +7060,77 @@ mod tests {
         fail_unless!(!contains(data, ~"ไท华"));
     }
 
+    #[test]
+    fn test_find_pat() {
+        let data = "ประเทศไทย中华Việt Nam";
+        let is_space: &fn(char) -> bool = |c| c == ' ';
+        fail_unless!(find_pat(data, '华') == Some(30u));
+        fail_unless!(find_pat(data, "Việt") == Some(33u));
+        fail_unless!(find_pat(data, is_space) == Some(39u));
+        fail_unless!(find_pat(data, 'z').is_none());
+        fail_unless!(find_pat(data, "Nam!").is_none());
+
+        fail_unless!("hello world".find_pat('o') == Some(4u));
+        fail_unless!("hello world".find_pat("wor") == Some(6u));
+        fail_unless!("hello world".find_pat(is_space) == Some(5u));
+        fail_unless!("hello".find_pat("") == Some(0u));
+    }
+
+    #[test]
+    fn test_find_all() {
+        fail_unless!(find_all("abcabc", "bc") == ~[1u, 4u]);
+        fail_unless!(find_all("abcabc", "") == ~[]);
+        fail_unless!(find_all("", "a") == ~[]);
+        fail_unless!(find_all("abc", "abcd") == ~[]);
+        fail_unless!(find_all("aaaa", "aa") == ~[0u, 2u]);
+        fail_unless!("中华中华".find_all("华") == ~[3u, 9u]);
+    }
+
+    #[test]
+    fn test_contains_at_least() {
+        fail_unless!(contains_at_least("abcabcabc", "abc", 3u));
+        fail_unless!(!contains_at_least("abcabcabc", "abc", 4u));
+        fail_unless!(contains_at_least("", "abc", 0u));
+        fail_unless!(contains_at_least("abc", "", 0u));
+        // an empty needle matches at each of the four boundaries
+        fail_unless!(contains_at_least("abc", "", 4u));
+        fail_unless!(!contains_at_least("abc", "", 5u));
+        fail_unless!(contains_at_least("中华", "", 3u));
+        fail_unless!(!contains_at_least("中华", "", 4u));
+        fail_unless!(contains_at_least("", "", 1u));
+        // matches do not overlap
+        fail_unless!(!contains_at_least("aaa", "aa", 2u));
+        fail_unless!("中华中华".contains_at_least("中华", 2u));
+
+        // the matches are all at the front of a long string
+        let mut big = repeat("x", 10u);
+        push_str(&mut big, repeat("y", 1024u * 1024u));
+        fail_unless!(contains_at_least(big, "x", 10u));
+        fail_unless!(!contains_at_least(big, "x", 11u));
+    }
+
+    #[test]
+    fn test_contains_ignore_case() {
+        fail_unless!(contains_ignore_case("Hello World", "LO WO"));
+        fail_unless!(contains_ignore_case("Hello World", "hello world"));
+        fail_unless!(contains_ignore_case("Hello World", ""));
+        fail_unless!(!contains_ignore_case("Hello World", "worlds"));
+        fail_unless!(!contains_ignore_case("", "a"));
+        fail_unless!("ประเทศ Việt NAM".contains_ignore_case("việt nam"));
+    }
+
+    #[test]
+    fn test_starts_ends_with_ignore_case() {
+        fail_unless!(starts_with_ignore_case("Content-Type: x", "content-type"));
+        fail_unless!(!starts_with_ignore_case("Content", "contents"));
+        fail_unless!(starts_with_ignore_case("abc", ""));
+        fail_unless!(ends_with_ignore_case("README.TXT", ".txt"));
+        fail_unless!(!ends_with_ignore_case("README.TXT", ".md"));
+        fail_unless!(ends_with_ignore_case("", ""));
+        fail_unless!("中华.PNG".ends_with_ignore_case("华.png"));
+        fail_unless!("Việt".starts_with_ignore_case("VIệ"));
+    }
+
     #[test]
     fn test_contains_char() {
         fail_unless!(contains_char(~"abc", 'b'));
@@ -3432,6 +7211,38 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_each_line() {
+        let lf = "\nMary had a little lamb\nLittle lamb\n";
+        let mut v = ~[];
+        for each_line(lf) |x| { v.push(x); }
+        fail_unless!(v == ~["", "Mary had a little lamb", "Little lamb"]);
+
+        let mut v = ~[];
+        for "a\r\nb\n\nc".each_line_any |x| { v.push(x); }
+        fail_unless!(v == ~["a", "b", "", "c"]);
+
+        for each_line("") |_x| { fail!(); }
+
+        let mut buf = ~"";
+        let mut i = 0u;
+        while i < 100000u {
+            push_str(&mut buf, "some log line");
+            push_char(&mut buf, '\n');
+            i += 1u;
+        }
+        let mut rebuilt = with_capacity(len(buf));
+        let mut n = 0u;
+        for buf.each_line |x| {
+            fail_unless!(x.len() == 13u);
+            push_str(&mut rebuilt, x);
+            push_char(&mut rebuilt, '\n');
+            n += 1u;
+        }
+        fail_unless!(n == 100000u);
+        fail_unless!(rebuilt == buf);
+    }
+
     #[test]
     fn test_map() {
         unsafe {
@@ -3657,6 +7468,71 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_each_char_with_last() {
+        let mut v = ~[];
+        for each_char_with_last("abc") |ch, last| { v.push((ch, last)); }
+        fail_unless!(v == ~[('a', false), ('b', false), ('c', true)]);
+
+        let mut out = ~"";
+        for "中华V".each_char_with_last |ch, last| {
+            push_char(&mut out, ch);
+            if !last { push_str(&mut out, ", "); }
+        }
+        fail_unless!(out == ~"中, 华, V");
+
+        for each_char_with_last("") |_ch, _last| { fail!(); }
+    }
+
+    #[test]
+    fn test_each_char_window() {
+        let mut v = ~[];
+        for each_char_window("中华V", 2u) |w| { v.push(w); }
+        fail_unless!(v == ~["中华", "华V"]);
+
+        let mut v = ~[];
+        for "abcd".each_char_window(3u) |w| { v.push(w); }
+        fail_unless!(v == ~["abc", "bcd"]);
+
+        let mut v = ~[];
+        for "ab".each_char_window(2u) |w| { v.push(w); }
+        fail_unless!(v == ~["ab"]);
+
+        for each_char_window("ab", 3u) |_w| { fail!(); }
+        for each_char_window("ab", 0u) |_w| { fail!(); }
+        for each_char_window("", 1u) |_w| { fail!(); }
+    }
+
+    #[test]
+    fn test_fold_chars() {
+        fail_unless!(fold_chars("abc", 0u, |n, c| n + (c as uint)) == 294u);
+        fail_unless!("".char_fold(7u, |n, c| n + (c as uint)) == 7u);
+        let rev = fold_chars("中华V", ~"", |acc, c| {
+            let mut s = from_char(c);
+            push_str(&mut s, acc);
+            s
+        });
+        fail_unless!(rev == ~"V华中");
+        fail_unless!("ประเทศ".char_fold(0u, |n, _c| n + 1u) == char_len("ประเทศ"));
+    }
+
+    #[test]
+    fn test_each_char_lossy() {
+        let mut s = ~"";
+        for [0x61_u8, 0xff_u8, 0xe4_u8, 0xb8_u8, 0xad_u8, 0x62_u8].each |b| {
+            unsafe { raw::push_byte(&mut s, *b); }
+        }
+        let mut v = ~[];
+        for each_char_lossy(s) |ch| { v.push(ch); }
+        fail_unless!(v == ~['a', '\uFFFD', '中', 'b']);
+
+        let mut v = ~[];
+        for "ศไทย中华".each_char_lossy |ch| { v.push(ch); }
+        fail_unless!(v == ~['ศ', 'ไ', 'ท', 'ย', '中', '华']);
+
+        for each_char_lossy("") |_ch| { fail!(); }
+    }
+
     #[test]
     fn test_each_char_reverse() {
         let s = ~"ศไทย中华Việt Nam";
@@ -3680,6 +7556,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_each_char_byte_reverse() {
+        let mut v = ~[];
+        for each_char_byte_reverse("中华V") |i, ch| { v.push((i, ch)); }
+        fail_unless!(v == ~[(6u, 'V'), (3u, '华'), (0u, '中')]);
+
+        let s = "ศไทย中华Việt Nam";
+        let mut last_upper = 0u;
+        for s.each_char_byte_reverse |i, ch| {
+            if ch == 'V' || ch == 'N' {
+                last_upper = i;
+                break;
+            }
+        }
+        fail_unless!(last_upper == 25u);
+        fail_unless!(slice(s, last_upper, len(s)) == "Nam");
+
+        for each_char_byte_reverse("") |_i, _ch| { fail!(); }
+    }
+
     #[test]
     fn test_escape_unicode() {
         fail_unless!(escape_unicode(~"abc") == ~"\\x61\\x62\\x63");
@@ -3708,10 +7604,61 @@ mod tests {
         fail_unless!(escape_default(~"\U0001d4ea\r") == ~"\\U0001d4ea\\r");
     }
 
+    #[test]
+    fn test_escape_html() {
+        fail_unless!(escape_html("<a href=\"x\">") ==
+                     ~"&lt;a href=&quot;x&quot;&gt;");
+        fail_unless!(escape_html("Tom & Jerry's") == ~"Tom &amp; Jerry&#39;s");
+        fail_unless!(escape_html("&amp;") == ~"&amp;amp;");
+        fail_unless!("plain text 中华".escape_html() == ~"plain text 中华");
+        fail_unless!(escape_html("") == ~"");
+    }
+
+    #[test]
+    fn test_unescape_default() {
+        let strs = [~"abc", ~"a c", ~"\r\n\t", ~"'\"\\", ~"\u0100\uffff",
+                    ~"\U00010000\U0010ffff", ~"ab\ufb00", ~"\U0001d4ea\r",
+                    ~"\x00\x1f\x7f", ~"中华", ~""];
+        for strs.each |s| {
+            fail_unless!(unescape_default(escape_default(*s)) == Some(copy *s));
+        }
+        fail_unless!(unescape_default("\\x41\\u4e2d") == Some(~"A中"));
+        fail_unless!(unescape_default("\\q").is_none());
+        fail_unless!(unescape_default("abc\\").is_none());
+        fail_unless!(unescape_default("\\x4").is_none());
+        fail_unless!(unescape_default("\\u12g4").is_none());
+        fail_unless!(unescape_default("\\U00110000").is_none());
+        fail_unless!(unescape_default("\\uD800").is_none());
+        fail_unless!(unescape_default("\\uDFFF").is_none());
+        fail_unless!(unescape_default("\\uD7FF") == Some(~"\uD7FF"));
+    }
+
+    #[test]
+    fn test_refcount() {
+        let s = "interned".to_managed();
+        let before = refcount(s);
+        let t = s;
+        fail_unless!(refcount(s) == before + 1u);
+        fail_unless!(refcount(t) == before + 1u);
+        fail_unless!(t == s);
+    }
+
     #[test]
     fn test_to_managed() {
         fail_unless!((~"abc").to_managed() == @"abc");
         fail_unless!(slice("abcdef", 1, 5).to_managed() == @"bcde");
+        fail_unless!((~"").to_managed() == @"");
+        fail_unless!("中华".to_managed() == @"中华");
+
+        let long = repeat("ประเทศไทย中华Việt Nam", 10000u);
+        let managed = long.to_managed();
+        fail_unless!(managed.len() == long.len());
+        fail_unless!(eq_slice(managed, long));
+        // the trailing null is in place for as_c_str and friends
+        do as_buf(managed) |buf, n| {
+            fail_unless!(n == long.len() + 1u);
+            fail_unless!(unsafe { *ptr::offset(buf, n - 1u) } == 0u8);
+        }
     }
 
     #[test]
@@ -3722,4 +7669,674 @@ mod tests {
         "12345555".cmp(& &"123456") == Less;
         "22".cmp(& &"1234") == Greater;
     }
+
+    #[test]
+    fn test_total_cmp() {
+        fail_unless!(total_cmp("1234", "123") == Greater);
+        fail_unless!(total_cmp("123", "1234") == Less);
+        fail_unless!(total_cmp("1234", "1234") == Equal);
+        fail_unless!(total_cmp("12345555", "123456") == Less);
+        fail_unless!(total_cmp("22", "1234") == Greater);
+        fail_unless!(total_cmp("", "") == Equal);
+        fail_unless!(total_cmp("", "a") == Less);
+        // bytes compare unsigned, so code point order is kept
+        fail_unless!(total_cmp("\xff", "a") == Greater);
+        fail_unless!(total_cmp("中", "华") == Less);
+        fail_unless!(total_cmp(slice("abcd", 0u, 2u), "abc") == Less);
+
+        // usable wherever a comparator is expected
+        fn min_by(v: &[&str], f: &fn(&str, &str) -> Ordering) -> ~str {
+            let mut best = v[0];
+            for v.each |s| { if f(*s, best) == Less { best = *s; } }
+            best.to_owned()
+        }
+        fail_unless!(min_by(["pear", "apple", "中", "fig"], total_cmp)
+                     == ~"apple");
+    }
+
+    #[test]
+    fn test_chars_cursor() {
+        let mut cur = chars_cursor("中华V");
+        fail_unless!(cur.byte_pos() == 0u);
+        fail_unless!(cur.peek() == Some('中'));
+        fail_unless!(cur.peek() == Some('中'));
+        fail_unless!(cur.next() == Some('中'));
+        fail_unless!(cur.byte_pos() == 3u);
+        fail_unless!(cur.peek() == Some('华'));
+        fail_unless!(cur.next() == Some('华'));
+        fail_unless!(cur.byte_pos() == 6u);
+        fail_unless!(cur.next() == Some('V'));
+        fail_unless!(cur.byte_pos() == 7u);
+        fail_unless!(cur.peek().is_none());
+        fail_unless!(cur.next().is_none());
+        fail_unless!(cur.byte_pos() == 7u);
+
+        let mut empty = chars_cursor("");
+        fail_unless!(empty.next().is_none());
+    }
+
+    #[test]
+    fn test_dedup_by_key() {
+        fn kind(c: char) -> uint {
+            if char::is_whitespace(c) { 0u } else { c as uint + 1u }
+        }
+        fail_unless!(dedup_by_key("a   b\t\tc", kind) == ~"a b c");
+        fail_unless!(dedup_by_key(" \t\n", kind) == ~" ");
+        fail_unless!(dedup_by_key("", kind) == ~"");
+        fail_unless!("aabbb".dedup_by_key(|c| c as uint) == ~"ab");
+        fail_unless!("a12b345".dedup_by_key(|c| {
+            if char::is_digit(c) { 0u } else { c as uint }
+        }) == ~"a1b3");
+    }
+
+    #[test]
+    fn test_common_prefix_len() {
+        fail_unless!(common_prefix_len("", "") == 0u);
+        fail_unless!(common_prefix_len("abc", "") == 0u);
+        fail_unless!(common_prefix_len("abc", "abd") == 2u);
+        fail_unless!(common_prefix_len("abc", "abc") == 3u);
+        fail_unless!(common_prefix_len("ab", "abc") == 2u);
+        fail_unless!(common_prefix_len("中文A", "中XB") == 3u);
+        // '中' and '丰' share their lead byte but are different chars
+        fail_unless!(common_prefix_len("中", "丰") == 0u);
+        fail_unless!("foobar".common_prefix_len("foobaz") == 5u);
+    }
+
+    #[test]
+    fn test_common_suffix_len() {
+        fail_unless!(common_suffix_len("", "") == 0u);
+        fail_unless!(common_suffix_len("abc", "") == 0u);
+        fail_unless!(common_suffix_len("abc", "xbc") == 2u);
+        fail_unless!(common_suffix_len("abc", "abc") == 3u);
+        fail_unless!(common_suffix_len("bc", "abc") == 2u);
+        fail_unless!(common_suffix_len("A文中", "X中") == 3u);
+        // '华' and 'Ў' share their final byte but are different chars
+        fail_unless!(common_suffix_len("华", "Ў") == 0u);
+        fail_unless!("foo.rs".common_suffix_len("bar.rs") == 3u);
+    }
+
+    #[test]
+    fn test_parse_int_auto() {
+        fail_unless!(parse_int_auto("0xFF") == Some(255));
+        fail_unless!(parse_int_auto("0Xff") == Some(255));
+        fail_unless!(parse_int_auto("0b1010") == Some(10));
+        fail_unless!(parse_int_auto("-0o17") == Some(-15));
+        fail_unless!(parse_int_auto("+0x10") == Some(16));
+        fail_unless!(parse_int_auto("42") == Some(42));
+        fail_unless!(parse_int_auto("-42") == Some(-42));
+        fail_unless!(parse_int_auto("0") == Some(0));
+        fail_unless!(parse_int_auto("0xG").is_none());
+        fail_unless!(parse_int_auto("0x").is_none());
+        fail_unless!(parse_int_auto("0b102").is_none());
+        fail_unless!(parse_int_auto("--1").is_none());
+        fail_unless!(parse_int_auto("0x-1").is_none());
+        fail_unless!(parse_int_auto("").is_none());
+        fail_unless!(parse_int_auto("-").is_none());
+    }
+
+    #[test]
+    fn test_levdistance() {
+        fail_unless!(levdistance("", "") == 0u);
+        fail_unless!(levdistance("abc", "") == 3u);
+        fail_unless!(levdistance("", "abc") == 3u);
+        fail_unless!(levdistance("kitten", "sitting") == 3u);
+        fail_unless!(levdistance("ab", "ba") == 2u);
+        // distances are counted in chars, not bytes
+        fail_unless!(levdistance("中", "中") == 0u);
+        fail_unless!(levdistance("中华", "中文") == 1u);
+        fail_unless!(levdistance("中华V", "") == 3u);
+        fail_unless!(levdistance("a", "华") == 1u);
+    }
+
+    #[test]
+    fn test_damerau_levdistance() {
+        fail_unless!(damerau_levdistance("", "") == 0u);
+        fail_unless!(damerau_levdistance("abc", "") == 3u);
+        fail_unless!(damerau_levdistance("kitten", "sitting") == 3u);
+        fail_unless!(damerau_levdistance("ab", "ba") == 1u);
+        fail_unless!(damerau_levdistance("abcd", "acbd") == 1u);
+        fail_unless!(damerau_levdistance("ca", "abc") == 3u);
+        fail_unless!(damerau_levdistance("中华", "华中") == 1u);
+    }
+
+    #[test]
+    fn test_similarity() {
+        fail_unless!(similarity("", "") == 1.0);
+        fail_unless!(similarity("abc", "abc") == 1.0);
+        fail_unless!(similarity("abc", "") == 0.0);
+        fail_unless!(similarity("abc", "xyz") == 0.0);
+        fail_unless!(similarity("kitten", "sitting") == 1.0 - 3.0 / 7.0);
+        fail_unless!(similarity("中华", "中文") == 0.5);
+    }
+
+    #[test]
+    fn test_levenshtein_ratio() {
+        fail_unless!(levenshtein_ratio("", "") == 1.0);
+        fail_unless!(levenshtein_ratio("hello", "hello") == 1.0);
+        fail_unless!(levenshtein_ratio("abcd", "wxyz") == 0.0);
+        fail_unless!(levenshtein_ratio("abcd", "abxy") == 0.5);
+        fail_unless!(levenshtein_ratio("中华", "") == 0.0);
+
+        let pairs = [("kitten", "sitting"), ("abc", "abcd"), ("中华", "华")];
+        for pairs.each |pair| {
+            let (a, b) = *pair;
+            fail_unless!(levenshtein_ratio(a, b) == levenshtein_ratio(b, a));
+        }
+    }
+
+    #[test]
+    fn test_count_graphemes() {
+        fail_unless!(count_graphemes("") == 0u);
+        fail_unless!(count_graphemes("e\u0301") == 1u);
+        fail_unless!(count_graphemes("ab") == 2u);
+        fail_unless!(count_graphemes("e\u0301\u0302x") == 2u);
+        fail_unless!(count_graphemes("\u0301a") == 2u);
+        fail_unless!(count_graphemes("中华Việt") == 6u);
+        let ascii = "hello world";
+        fail_unless!(ascii.count_graphemes() == ascii.char_len());
+    }
+
+    #[test]
+    fn test_as_bytes_slice() {
+        let v = as_bytes_slice("abc");
+        fail_unless!(v.len() == 3u);
+        fail_unless!(v[2] == 'c' as u8);
+        fail_unless!(as_bytes_slice("").len() == 0u);
+
+        let sub = slice("abcdef", 1u, 3u);
+        let v = as_bytes_slice(sub);
+        fail_unless!(v.len() == 2u);
+        fail_unless!(v[0] == 'b' as u8 && v[1] == 'c' as u8);
+
+        fail_unless!(as_bytes_slice("中").len() == 3u);
+    }
+
+    #[test]
+    fn test_bytes_iter() {
+        let mut seen = ~[];
+        for bytes_iter("abc") |i, b| { seen.push((i, b)); }
+        fail_unless!(seen == ~[(0u, 'a' as u8), (1u, 'b' as u8),
+                               (2u, 'c' as u8)]);
+
+        let mut n = 0u;
+        for bytes_iter("中华") |_i, _b| { n += 1u; }
+        fail_unless!(n == 6u);
+
+        for bytes_iter("") |_i, _b| { fail!(); }
+
+        let mut n = 0u;
+        for bytes_iter("abcdef") |i, _b| { n += 1u; if i == 2u { break; } }
+        fail_unless!(n == 3u);
+    }
+
+    #[test]
+    fn test_replace_between() {
+        fail_unless!(replace_between("pre[old]post", "[", "]", "new")
+                     == Some(~"pre[new]post"));
+        fail_unless!(replace_between("pre[old", "[", "]", "new").is_none());
+        fail_unless!(replace_between("pre]old[", "[", "]", "new").is_none());
+        fail_unless!(replace_between("old]", "[", "]", "new").is_none());
+        fail_unless!(replace_between("[]", "[", "]", "x") == Some(~"[x]"));
+        fail_unless!(replace_between("[a][b]", "[", "]", "") == Some(~"[][b]"));
+        fail_unless!(replace_between("<body>中华</body>", "<body>", "</body>",
+                                     "Việt")
+                     == Some(~"<body>Việt</body>"));
+    }
+
+    #[test]
+    fn test_mask() {
+        fail_unless!(mask("1234567890", 2u, 2u, '*') == ~"12******90");
+        fail_unless!(mask("1234567890", 0u, 4u, '#') == ~"######7890");
+        fail_unless!(mask("1234567890", 3u, 0u, '*') == ~"123*******");
+        fail_unless!(mask("abcd", 2u, 2u, '*') == ~"abcd");
+        fail_unless!(mask("ab", 5u, 0u, '*') == ~"ab");
+        fail_unless!(mask("", 0u, 0u, '*') == ~"");
+        fail_unless!(mask("中华Việt", 1u, 1u, '·') == ~"中····t");
+    }
+
+    #[test]
+    fn test_to_ascii_upper() {
+        fail_unless!(to_ascii_upper("") == ~"");
+        fail_unless!(to_ascii_upper("abcDEF xyz:.;") == ~"ABCDEF XYZ:.;");
+        fail_unless!(to_ascii_upper("café") == ~"CAFé");
+        fail_unless!("get".to_ascii_upper() == ~"GET");
+        fail_unless!("中华việt".to_ascii_upper() == ~"中华VIệT");
+    }
+
+    #[test]
+    fn test_to_ascii_lower() {
+        fail_unless!(to_ascii_lower("") == ~"");
+        fail_unless!(to_ascii_lower("abcDEF XYZ:.;") == ~"abcdef xyz:.;");
+        fail_unless!(to_ascii_lower("CAFÉ") == ~"cafÉ");
+        fail_unless!("0XFF".to_ascii_lower() == ~"0xff");
+        fail_unless!("中华VIỆT".to_ascii_lower() == ~"中华viỆt");
+    }
+
+    #[test]
+    fn test_longest_repeated_substring() {
+        fail_unless!(longest_repeated_substring("banana") == "ana");
+        fail_unless!(longest_repeated_substring("abc") == "");
+        fail_unless!(longest_repeated_substring("") == "");
+        fail_unless!(longest_repeated_substring("aaaa") == "aaa");
+        fail_unless!(longest_repeated_substring("xyzxyabab") == "xy");
+        fail_unless!(longest_repeated_substring("中华V中华") == "中华");
+        fail_unless!(longest_repeated_substring("ประเทศไทย") == "ท");
+    }
+
+    #[test]
+    fn test_eq_slice_sub_slices() {
+        unsafe {
+            let suffix = raw::slice_bytes("foobar", 3u, 6u);
+            fail_unless!(eq_slice(suffix, "bar"));
+            fail_unless!(eq_slice("bar", suffix));
+            fail_unless!(!eq_slice(suffix, "ba"));
+            fail_unless!(!eq_slice(suffix, "barr"));
+
+            let abcabc = "abcabc";
+            let first = raw::slice_bytes(abcabc, 0u, 3u);
+            let second = raw::slice_bytes(abcabc, 3u, 6u);
+            fail_unless!(eq_slice(first, second));
+            fail_unless!(!eq_slice(raw::slice_bytes(abcabc, 0u, 2u),
+                                   raw::slice_bytes(abcabc, 1u, 3u)));
+
+            let empty = raw::slice_bytes("abc", 3u, 3u);
+            fail_unless!(eq_slice(empty, ""));
+            fail_unless!(eq_slice(raw::slice_bytes("abc", 1u, 1u), empty));
+
+            let data = "中华中华";
+            fail_unless!(eq_slice(raw::slice_bytes(data, 0u, 6u),
+                                  raw::slice_bytes(data, 6u, 12u)));
+        }
+    }
+
+    #[test]
+    fn test_wrap_with_prefix() {
+        let text = "the quick brown fox jumps over the lazy dog";
+        let wrapped = wrap_with_prefix(text, 20u, "// ");
+        fail_unless!(wrapped ==
+                     ~"// the quick brown\n// fox jumps over\n// the lazy dog");
+        for lines_each(wrapped) |line| {
+            fail_unless!(line.char_len() <= 20u);
+            fail_unless!(starts_with(line, "// "));
+        }
+
+        fail_unless!(wrap_with_prefix("a verylongunbreakableword b", 10u, "# ")
+                     == ~"# a\n# verylongunbreakableword\n# b");
+        fail_unless!(wrap_with_prefix("  single  ", 20u, "> ") == ~"> single");
+        fail_unless!(wrap_with_prefix("", 20u, "// ") == ~"");
+        fail_unless!(wrap_with_prefix("中华 Việt Nam", 9u, "» ")
+                     == ~"» 中华 Việt\n» Nam");
+    }
+
+    #[test]
+    fn test_ascii_lower_into() {
+        let mut a = ~"", b = ~"";
+        ascii_lower_into("ReadMe.TXT", &mut a);
+        ascii_lower_into("README.txt", &mut b);
+        fail_unless!(a == ~"readme.txt");
+        fail_unless!(a == b);
+
+        let cap = capacity(&a);
+        ascii_lower_into("Makefile", &mut a);
+        fail_unless!(a == ~"makefile");
+        fail_unless!(capacity(&a) == cap);
+
+        ascii_lower_into("", &mut a);
+        fail_unless!(a == ~"");
+        ascii_lower_into("CAFÉ", &mut a);
+        fail_unless!(a == ~"cafÉ");
+    }
+
+    #[test]
+    fn test_make_ascii_upper_lower() {
+        let mut s = ~"ABC中";
+        let cap = capacity(&s);
+        let p = do as_buf(s) |buf, _len| { buf };
+        make_ascii_lower(&mut s);
+        fail_unless!(s == ~"abc中");
+        fail_unless!(capacity(&s) == cap);
+        let q = do as_buf(s) |buf, _len| { buf };
+        fail_unless!(q == p);
+
+        s.make_ascii_upper();
+        fail_unless!(s == ~"ABC中");
+
+        let mut s = ~"Café 42!";
+        s.make_ascii_upper();
+        fail_unless!(s == ~"CAFé 42!");
+        s.make_ascii_lower();
+        fail_unless!(s == ~"café 42!");
+
+        let mut s = ~"";
+        s.make_ascii_upper();
+        fail_unless!(s == ~"");
+    }
+
+    #[test]
+    fn test_reverse_ascii() {
+        let mut s = ~"abcd";
+        let cap = capacity(&s);
+        reverse_ascii(&mut s);
+        fail_unless!(s == ~"dcba");
+        fail_unless!(capacity(&s) == cap);
+
+        let mut s = ~"abc";
+        s.reverse_ascii();
+        fail_unless!(s == ~"cba");
+        let mut s = ~"x";
+        s.reverse_ascii();
+        fail_unless!(s == ~"x");
+        let mut s = ~"";
+        s.reverse_ascii();
+        fail_unless!(s == ~"");
+    }
+
+    #[test]
+    #[should_fail]
+    #[ignore(cfg(windows))]
+    fn test_reverse_ascii_non_ascii() {
+        let mut s = ~"a中";
+        s.reverse_ascii();
+    }
+
+    #[test]
+    fn test_each_matching_run() {
+        let mut runs = ~[];
+        for each_matching_run("ab12cd345", char::is_digit) |run| {
+            runs.push(run.to_owned());
+        }
+        fail_unless!(runs == ~[~"12", ~"345"]);
+
+        let mut runs = ~[];
+        for "中华 Việt  Nam".each_matching_run(|c| !char::is_whitespace(c))
+            |run| {
+            runs.push(run.to_owned());
+        }
+        fail_unless!(runs == ~[~"中华", ~"Việt", ~"Nam"]);
+
+        let mut n = 0u;
+        for each_matching_run("1a2b3c", char::is_digit) |_run| {
+            n += 1u;
+            if n == 2u { break; }
+        }
+        fail_unless!(n == 2u);
+
+        for each_matching_run("", char::is_digit) |_run| { fail!(); }
+        for each_matching_run("abc", char::is_digit) |_run| { fail!(); }
+    }
+
+    #[test]
+    fn test_split_nul() {
+        let mut buf = ~"PATH=/bin";
+        push_char(&mut buf, '\x00');
+        push_str(&mut buf, "LANG=中华");
+        push_char(&mut buf, '\x00');
+        push_char(&mut buf, '\x00');
+        push_str(&mut buf, "X=1");
+
+        let mut parts = ~[];
+        for split_nul(buf) |part| { parts.push(part.to_owned()); }
+        fail_unless!(parts == ~[~"PATH=/bin", ~"LANG=中华", ~"", ~"X=1"]);
+
+        let mut parts = ~[];
+        for split_nul(buf) |part| {
+            parts.push(part.to_owned());
+            if parts.len() == 2u { break; }
+        }
+        fail_unless!(parts == ~[~"PATH=/bin", ~"LANG=中华"]);
+
+        let mut parts = ~[];
+        for split_nul("abc") |part| { parts.push(part.to_owned()); }
+        fail_unless!(parts == ~[~"abc"]);
+
+        let mut parts = ~[];
+        for split_nul("a\x00") |part| { parts.push(part.to_owned()); }
+        fail_unless!(parts == ~[~"a", ~""]);
+    }
+
+    #[test]
+    fn test_each_word() {
+        let data = "\nMary had a little lamb\nLittle lamb\n \u3000中华  Việt Nam\t";
+        let mut n = 0u;
+        for each_word(data) |_w| { n += 1u; }
+        fail_unless!(n == words(data).len());
+
+        let mut ws = ~[];
+        for data.each_word |w| { ws.push(w.to_owned()); }
+        fail_unless!(ws == words(data));
+
+        let mut ws = ~[];
+        for each_word(" a b c ") |w| {
+            ws.push(w.to_owned());
+            if ws.len() == 2u { break; }
+        }
+        fail_unless!(ws == ~[~"a", ~"b"]);
+
+        for each_word("") |_w| { fail!(); }
+        for each_word(" \t\n") |_w| { fail!(); }
+    }
+
+    #[test]
+    fn test_each_case_run() {
+        let mut runs = ~[];
+        for each_case_run("fooBarBAZ") |run| { runs.push(run.to_owned()); }
+        fail_unless!(runs == ~[~"foo", ~"B", ~"ar", ~"BAZ"]);
+
+        let mut runs = ~[];
+        for "getHTTP2Ответ".each_case_run |run| { runs.push(run.to_owned()); }
+        fail_unless!(runs == ~[~"get", ~"HTTP", ~"2", ~"О", ~"твет"]);
+
+        let mut runs = ~[];
+        for each_case_run("aBcD") |run| {
+            runs.push(run.to_owned());
+            if runs.len() == 2u { break; }
+        }
+        fail_unless!(runs == ~[~"a", ~"B"]);
+
+        for each_case_run("") |_run| { fail!(); }
+    }
+
+    #[test]
+    fn test_each_token() {
+        let mut toks = ~[];
+        for each_token("foo, bar!", char::is_alphanumeric) |i, tok| {
+            toks.push((i, tok.to_owned()));
+        }
+        fail_unless!(toks == ~[(0u, ~"foo"), (5u, ~"bar")]);
+
+        let mut toks = ~[];
+        for "中华 Việt".each_token(char::is_alphanumeric) |i, tok| {
+            toks.push((i, tok.to_owned()));
+        }
+        fail_unless!(toks == ~[(0u, ~"中华"), (7u, ~"Việt")]);
+
+        let mut n = 0u;
+        for each_token("a b c", char::is_alphanumeric) |_i, _tok| {
+            n += 1u;
+            if n == 2u { break; }
+        }
+        fail_unless!(n == 2u);
+    }
+
+    #[test]
+    fn test_char_range_at_opt() {
+        let s = "a中华";
+        match char_range_at_opt(s, 0u) {
+            Some(CharRange {ch, next}) => {
+                fail_unless!(ch == 'a' && next == 1u);
+            }
+            _ => fail!()
+        }
+        match char_range_at_opt(s, 4u) {
+            Some(CharRange {ch, next}) => {
+                fail_unless!(ch == '华' && next == 7u);
+            }
+            _ => fail!()
+        }
+        fail_unless!(char_range_at_opt(s, 7u).is_none());
+        fail_unless!(char_range_at_opt(s, 100u).is_none());
+        fail_unless!(char_range_at_opt(s, 2u).is_none());
+        fail_unless!(char_range_at_opt("", 0u).is_none());
+
+        // Truncated multibyte tail
+        let mut truncated = ~"a中";
+        unsafe { raw::set_len(&mut truncated, 3u); }
+        fail_unless!(char_range_at_opt(truncated, 0u).is_some());
+        fail_unless!(char_range_at_opt(truncated, 1u).is_none());
+
+        // Malformed continuation byte
+        let mut bad = ~"";
+        unsafe {
+            raw::push_byte(&mut bad, 0xe4u8);
+            raw::push_byte(&mut bad, 'a' as u8);
+            raw::push_byte(&mut bad, 'b' as u8);
+        }
+        fail_unless!(char_range_at_opt(bad, 0u).is_none());
+        fail_unless!(char_range_at_opt(bad, 1u).is_some());
+    }
+
+    #[test]
+    fn test_char_at_opt() {
+        fail_unless!(char_at_opt("中", 0u) == Some('中'));
+        fail_unless!(char_at_opt("中", 1u).is_none());
+        fail_unless!(char_at_opt("ab", 1u) == Some('b'));
+        fail_unless!(char_at_opt("ab", 2u).is_none());
+        fail_unless!(char_at_opt("ab", 5u).is_none());
+        fail_unless!("a中".char_at_opt(1u) == Some('中'));
+    }
+
+    #[test]
+    fn test_char_boundary_floor_ceil() {
+        let s = "a中b";
+        fail_unless!(char_boundary_floor(s, 0u) == 0u);
+        fail_unless!(char_boundary_floor(s, 1u) == 1u);
+        fail_unless!(char_boundary_floor(s, 2u) == 1u);
+        fail_unless!(char_boundary_floor(s, 3u) == 1u);
+        fail_unless!(char_boundary_floor(s, 4u) == 4u);
+        fail_unless!(char_boundary_floor(s, 5u) == 5u);
+        fail_unless!(char_boundary_ceil(s, 2u) == 4u);
+        fail_unless!(char_boundary_ceil(s, 3u) == 4u);
+        fail_unless!(char_boundary_ceil(s, 1u) == 1u);
+        fail_unless!(char_boundary_ceil(s, 5u) == 5u);
+        fail_unless!("中华".char_boundary_floor(len("中华") / 2u) == 3u);
+        fail_unless!("中".char_boundary_ceil(1u) == 3u);
+        fail_unless!(char_boundary_floor("", 0u) == 0u);
+    }
+
+    #[test]
+    #[should_fail]
+    #[ignore(cfg(windows))]
+    fn test_char_boundary_floor_past_end() {
+        char_boundary_floor("ab", 3u);
+    }
+
+    #[test]
+    fn test_is_valid_range() {
+        let s = "中华Việt";
+        fail_unless!(is_valid_range(s, 3u, 7u));
+        fail_unless!(is_valid_range(s, 0u, len(s)));
+        fail_unless!(is_valid_range(s, 6u, 6u));
+        fail_unless!(slice(s, 3u, 7u) == "华V");
+        // reversed
+        fail_unless!(!is_valid_range(s, 7u, 3u));
+        // out of bounds
+        fail_unless!(!s.is_valid_range(0u, len(s) + 1u));
+        fail_unless!(!s.is_valid_range(20u, 30u));
+        // mid-character
+        fail_unless!(!is_valid_range(s, 1u, 6u));
+        fail_unless!(!is_valid_range(s, 0u, 9u));
+    }
+
+    #[test]
+    fn test_line_col() {
+        let s = "fn main() {\n    let 中华 = 1;\n}\n";
+        fail_unless!(line_col(s, 0u) == (1u, 1u));
+        fail_unless!(line_col(s, 3u) == (1u, 4u));
+        // the newline itself is the last column of its line
+        fail_unless!(line_col(s, 11u) == (1u, 12u));
+        fail_unless!(line_col(s, 12u) == (2u, 1u));
+        // "=", after the two 3-byte chars
+        let eq = find_char(s, '=').get();
+        fail_unless!(eq == 27u);
+        fail_unless!(s.line_col(eq) == (2u, 12u));
+        fail_unless!(line_col(s, len(s)) == (4u, 1u));
+        fail_unless!(line_col("", 0u) == (1u, 1u));
+    }
+
+    #[test]
+    #[should_fail]
+    #[ignore(cfg(windows))]
+    fn test_line_col_mid_char() {
+        line_col("a中", 2u);
+    }
+
+    #[test]
+    fn test_byte_at() {
+        fail_unless!(byte_at("abc", 0u) == Some(97u8));
+        fail_unless!(byte_at("abc", 2u) == Some(99u8));
+        // one past the end is the null terminator, which is not returned
+        fail_unless!(byte_at("abc", 3u).is_none());
+        fail_unless!(byte_at("abc", 100u).is_none());
+        fail_unless!(byte_at("", 0u).is_none());
+        fail_unless!("中".byte_at(1u) == Some(0xb8_u8));
+        fail_unless!(slice("abcd", 0u, 2u).byte_at(2u).is_none());
+    }
+
+    #[test]
+    fn test_nth_char_opt() {
+        fail_unless!(nth_char_opt("中华Việt", 0u) == Some('中'));
+        fail_unless!(nth_char_opt("中华Việt", 4u) == Some('ệ'));
+        fail_unless!(nth_char_opt("中华Việt", 6u).is_none());
+        fail_unless!(nth_char_opt("", 0u).is_none());
+        fail_unless!("ab".nth_char_opt(1u) == Some('b'));
+    }
+
+    #[test]
+    fn test_percent_encode() {
+        fail_unless!(percent_encode("", []) == ~"");
+        fail_unless!(percent_encode("abcXYZ019", []) == ~"abcXYZ019");
+        fail_unless!(percent_encode("a b/中", [])
+                     == ~"a%20b%2F%E4%B8%AD");
+        fail_unless!(percent_encode("a b/中", ['/' as u8, '-' as u8])
+                     == ~"a%20b/%E4%B8%AD");
+        fail_unless!(percent_encode("100%", []) == ~"100%25");
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        fail_unless!(percent_decode("") == Some(~[]));
+        fail_unless!(percent_decode("a%20b%2f%E4%B8%AD")
+                     == Some(to_bytes("a b/中")));
+        let unreserved = ['/' as u8, '.' as u8];
+        for ["a b/中", "", "100%", "x.y/z?q=1&r=2"].each |s| {
+            fail_unless!(percent_decode(percent_encode(*s, unreserved))
+                         == Some(to_bytes(*s)));
+        }
+        fail_unless!(percent_decode("%ff") == Some(~[0xff_u8]));
+        fail_unless!(percent_decode("%G0").is_none());
+        fail_unless!(percent_decode("%0G").is_none());
+        fail_unless!(percent_decode("abc%4").is_none());
+        fail_unless!(percent_decode("%").is_none());
+    }
+
+    #[test]
+    fn test_debug_str() {
+        fail_unless!(contains(debug_str(&~"hi"), "hi"));
+        fail_unless!(contains(debug_str(&(1, 'x')), "1"));
+    }
+
+    #[test]
+    fn test_hex() {
+        fail_unless!(to_hex("") == ~"");
+        fail_unless!(to_hex("a中") == ~"61e4b8ad");
+        fail_unless!(bytes_to_hex([0u8, 15u8, 16u8, 255u8]) == ~"000f10ff");
+        fail_unless!(hex_to_bytes("000F10ff") == Some(~[0u8, 15u8, 16u8, 255u8]));
+        for [~[], ~[0u8], ~[0xde_u8, 0xad_u8, 0xbe_u8, 0xef_u8],
+             to_bytes("ประเทศไทย中华")].each |v| {
+            fail_unless!(hex_to_bytes(bytes_to_hex(*v)) == Some(copy *v));
+        }
+        fail_unless!(hex_to_bytes("abc").is_none());
+        fail_unless!(hex_to_bytes("0g").is_none());
+        fail_unless!(hex_to_bytes("中a").is_none());
+    }
 }
@@ -126,6 +126,42 @@ pub fn refcount<T>(t: @T) -> uint {
     }
 }
 
+/**
+ * Splits a borrowed closure into its code and environment pointers
+ *
+ * This is unsafe because `F` is not checked to be a closure type. It must
+ * be a borrowed closure such as `&fn(char) -> bool`; any other type of the
+ * same size is reinterpreted as a closure, which is undefined behaviour.
+ * The environment pointer is only valid for as long as the closure is.
+ *
+ * # Failure
+ *
+ * Fails if `F` is not the size of a closure
+ */
+pub unsafe fn closure_parts<F>(f: F) -> (*(), *()) {
+    fail_unless!(size_of::<F>() == size_of::<Closure>());
+    let c: Closure = cast::transmute(f);
+    (c.code, c.env)
+}
+
+/**
+ * Rebuilds a borrowed closure from the pointers given by `closure_parts`
+ *
+ * This is unsafe because neither the pointers nor `F` can be checked. `F`
+ * must be exactly the type of the closure the parts were taken from, and
+ * that closure's environment must still be live; calling the result
+ * otherwise is undefined behaviour.
+ *
+ * # Failure
+ *
+ * Fails if `F` is not the size of a closure
+ */
+pub unsafe fn rebuild_closure<F>(parts: (*(), *())) -> F {
+    fail_unless!(size_of::<F>() == size_of::<Closure>());
+    let (code, env) = parts;
+    cast::transmute(Closure { code: code, env: env })
+}
+
 pub fn log_str<T>(t: &T) -> ~str {
     unsafe {
         do io::with_str_writer |wr| {
@@ -166,7 +202,9 @@ pub fn fail_assert(msg: &str, file: &str, line: uint) -> ! {
 #[cfg(test)]
 pub mod tests {
     use cast;
+    use str;
     use sys::{Closure, pref_align_of, size_of, nonzero_size_of};
+    use sys::{closure_parts, rebuild_closure};
 
     #[test]
     pub fn size_of_basic() {
@@ -245,6 +283,32 @@ pub mod tests {
             fail_unless!(new_f(20) == 30);
         }
     }
+
+    #[test]
+    pub fn rebuild_closure_from_parts() {
+        unsafe {
+            let x = 10;
+            let f: &fn(int) -> int = |y| x + y;
+            let new_f: &fn(int) -> int = rebuild_closure(closure_parts(f));
+            fail_unless!(new_f(20) == 30);
+
+            let sep = ',';
+            let is_sep: &fn(char) -> bool = |c| c == sep;
+            let parts = closure_parts(is_sep);
+            let cached: &fn(char) -> bool = rebuild_closure(parts);
+            fail_unless!(cached(','));
+            fail_unless!(!cached('a'));
+            fail_unless!(str::split("a,b", cached) == ~[~"a", ~"b"]);
+            fail_unless!(str::find("ab,", cached).get() == 2u);
+        }
+    }
+
+    #[test]
+    #[should_fail]
+    #[ignore(cfg(windows))]
+    pub fn closure_parts_of_non_closure() {
+        unsafe { closure_parts(5u); }
+    }
 }
 
 // Local Variables: